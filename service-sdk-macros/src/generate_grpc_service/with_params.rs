@@ -4,13 +4,14 @@ use syn::parse_macro_input;
 use quote::quote;
 use syn::{
     parse::{Parse, ParseStream},
-    Ident, Path, Result, Token, Type,
+    Expr, Ident, Path, Result, Token, Type,
 };
 
 struct GenerateGrpcServiceArgs {
     service_ident: Ident,
     app_ty: Type,
     server_path: Path,
+    file_descriptor_set: Option<Expr>,
 }
 
 impl Parse for GenerateGrpcServiceArgs {
@@ -23,10 +24,19 @@ impl Parse for GenerateGrpcServiceArgs {
 
         let server_path: Path = input.parse()?;
 
+        let mut file_descriptor_set = None;
+        if input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+            if !input.is_empty() {
+                file_descriptor_set = Some(input.parse::<Expr>()?);
+            }
+        }
+
         Ok(Self {
             service_ident,
             app_ty,
             server_path,
+            file_descriptor_set,
         })
     }
 }
@@ -36,8 +46,16 @@ pub fn with_params(input: TokenStream) -> TokenStream {
         service_ident,
         app_ty,
         server_path,
+        file_descriptor_set,
     } = parse_macro_input!(input as GenerateGrpcServiceArgs);
 
+    let file_descriptor_set = match file_descriptor_set {
+        Some(file_descriptor_set) => quote! {
+            const FILE_DESCRIPTOR_SET: Option<&'static [u8]> = Some(#file_descriptor_set);
+        },
+        None => quote! {},
+    };
+
     let expanded = quote! {
         #[derive(Clone)]
         pub struct #service_ident {
@@ -53,9 +71,32 @@ pub fn with_params(input: TokenStream) -> TokenStream {
         impl service_sdk::IntoGrpcServer for #service_ident {
             type GrpcServer = #server_path<Self>;
 
+            #file_descriptor_set
+
             fn into_grpc_server(self) -> Self::GrpcServer {
                 #server_path::new(self)
             }
+
+            fn into_grpc_server_with_options(
+                self,
+                options: &service_sdk::GrpcServerOptions,
+            ) -> Self::GrpcServer {
+                let mut server = #server_path::new(self);
+
+                if let Some(limit) = options.max_decoding_message_size {
+                    server = server.max_decoding_message_size(limit);
+                }
+
+                if let Some(limit) = options.max_encoding_message_size {
+                    server = server.max_encoding_message_size(limit);
+                }
+
+                for encoding in options.get_compression_encodings() {
+                    server = server.accept_compressed(encoding).send_compressed(encoding);
+                }
+
+                server
+            }
         }
     };
 
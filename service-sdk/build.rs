@@ -0,0 +1,28 @@
+use std::process::Command;
+
+fn main() {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+
+    let rustc_version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .and_then(|output| output.split_whitespace().nth(1).map(|itm| itm.to_string()))
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!(
+        "cargo:rustc-env=SERVICE_SDK_RUSTC_VERSION={}",
+        rustc_version
+    );
+
+    if let Ok(git_sha) = std::env::var("GIT_SHA") {
+        println!("cargo:rustc-env=SERVICE_SDK_GIT_SHA={}", git_sha);
+    }
+
+    // Unstable tokio runtime metrics are read when the service is built with `--cfg tokio_unstable`.
+    println!("cargo:rustc-check-cfg=cfg(tokio_unstable)");
+
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    println!("cargo:rerun-if-changed=build.rs");
+}
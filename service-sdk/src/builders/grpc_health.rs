@@ -0,0 +1,155 @@
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use rust_extensions::{AppStates, MyTimerTick};
+use tonic_health::{server::HealthReporter, ServingStatus};
+
+use crate::{GracefulShutdown, HealthChecks, HealthStatus};
+
+const STATUS_UNKNOWN: u8 = 0;
+const STATUS_SERVING: u8 = 1;
+const STATUS_NOT_SERVING: u8 = 2;
+
+pub struct GrpcHealthStatus {
+    reporter: HealthReporter,
+    service_names: Vec<&'static str>,
+    last_status: AtomicU8,
+    stopped: AtomicBool,
+    // A timer tick which started before shutdown must not finish after it and report SERVING again.
+    update_lock: tokio::sync::Mutex<()>,
+}
+
+impl GrpcHealthStatus {
+    pub(crate) fn new(reporter: HealthReporter, service_names: Vec<&'static str>) -> Self {
+        Self {
+            reporter,
+            service_names,
+            last_status: AtomicU8::new(STATUS_UNKNOWN),
+            stopped: AtomicBool::new(false),
+            update_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Reports NOT_SERVING for good. Later `set_serving(true)` calls are ignored.
+    pub async fn stop_serving(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        self.set_serving(false).await;
+    }
+
+    pub async fn set_serving(&self, serving: bool) {
+        let _update_lock = self.update_lock.lock().await;
+
+        let serving = serving && !self.stopped.load(Ordering::Relaxed);
+
+        let (status, status_as_u8) = if serving {
+            (ServingStatus::Serving, STATUS_SERVING)
+        } else {
+            (ServingStatus::NotServing, STATUS_NOT_SERVING)
+        };
+
+        if self.last_status.swap(status_as_u8, Ordering::Relaxed) == status_as_u8 {
+            return;
+        }
+
+        self.reporter.set_service_status("", status).await;
+        for service_name in self.service_names.iter() {
+            self.reporter.set_service_status(service_name, status).await;
+        }
+    }
+}
+
+pub(crate) struct GrpcHealthTimerTick {
+    pub grpc_health_status: Arc<GrpcHealthStatus>,
+    pub app_states: Arc<AppStates>,
+    pub graceful_shutdown: Arc<GracefulShutdown>,
+    pub health_checks: Option<Arc<HealthChecks>>,
+}
+
+#[async_trait]
+impl MyTimerTick for GrpcHealthTimerTick {
+    async fn tick(&self) {
+        let mut serving = self.app_states.is_initialized() && self.graceful_shutdown.is_ready();
+
+        if serving {
+            if let Some(health_checks) = self.health_checks.as_ref() {
+                serving = health_checks.get_report().await.status != HealthStatus::Unhealthy;
+            }
+        }
+
+        self.grpc_health_status.set_serving(serving).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic_health::pb::health_check_response::ServingStatus as PbServingStatus;
+    use tonic_health::pb::health_server::Health;
+    use tonic_health::pb::HealthCheckRequest;
+    use tonic_health::server::HealthService;
+
+    use super::*;
+
+    const SERVICE_NAME: &str = "orders.OrdersService";
+
+    fn get_health_status() -> (Arc<GrpcHealthStatus>, HealthService) {
+        let (reporter, _) = tonic_health::server::health_reporter();
+        let health_service = HealthService::from_health_reporter(reporter.clone());
+        let health_status = Arc::new(GrpcHealthStatus::new(reporter, vec![SERVICE_NAME]));
+        (health_status, health_service)
+    }
+
+    async fn get_status(health_service: &HealthService, service: &str) -> PbServingStatus {
+        let request = my_grpc_extensions::tonic::Request::new(HealthCheckRequest {
+            service: service.to_string(),
+        });
+
+        health_service
+            .check(request)
+            .await
+            .unwrap()
+            .into_inner()
+            .status()
+    }
+
+    async fn assert_status(health_service: &HealthService, expected: PbServingStatus) {
+        assert_eq!(get_status(health_service, "").await, expected);
+        assert_eq!(get_status(health_service, SERVICE_NAME).await, expected);
+    }
+
+    #[tokio::test]
+    async fn test_stopped_status_is_not_serving_for_good() {
+        let (health_status, health_service) = get_health_status();
+
+        health_status.set_serving(true).await;
+        assert_status(&health_service, PbServingStatus::Serving).await;
+
+        health_status.stop_serving().await;
+        assert_status(&health_service, PbServingStatus::NotServing).await;
+
+        health_status.set_serving(true).await;
+        assert_status(&health_service, PbServingStatus::NotServing).await;
+    }
+
+    // The timer still sees a ready application: the stop wins anyway.
+    #[tokio::test]
+    async fn test_timer_tick_after_stop_is_not_serving() {
+        let (health_status, health_service) = get_health_status();
+        let graceful_shutdown = Arc::new(GracefulShutdown::new());
+
+        let timer_tick = GrpcHealthTimerTick {
+            grpc_health_status: health_status.clone(),
+            app_states: Arc::new(AppStates::create_initialized()),
+            graceful_shutdown: graceful_shutdown.clone(),
+            health_checks: None,
+        };
+
+        timer_tick.tick().await;
+        assert_status(&health_service, PbServingStatus::Serving).await;
+
+        health_status.stop_serving().await;
+
+        timer_tick.tick().await;
+        assert_status(&health_service, PbServingStatus::NotServing).await;
+    }
+}
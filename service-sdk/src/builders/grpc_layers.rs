@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use my_grpc_extensions::hyper::body::Bytes;
+use my_grpc_extensions::hyper::{Request, Response};
+use my_grpc_extensions::tonic::body::Body;
+use tower::{Layer, Service, ServiceExt};
+
+use crate::GrpcMetricsMiddlewareLayer;
+
+pub type GrpcBoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Type-erased service every custom gRPC layer wraps and returns.
+pub type GrpcBoxService = tower::util::BoxCloneService<Request<Body>, Response<Body>, GrpcBoxError>;
+
+/// Layer stack of the SDK gRPC routers: metrics outermost, then the custom layers.
+pub type GrpcServerLayer = tower::layer::util::Stack<
+    GrpcCustomLayers,
+    tower::layer::util::Stack<GrpcMetricsMiddlewareLayer, tower::layer::util::Identity>,
+>;
+
+type GrpcLayerFn = Arc<dyn Fn(GrpcBoxService) -> GrpcBoxService + Send + Sync>;
+
+/// Custom tower layers registered with `GrpcServerBuilder::add_layer`. The layer added first is the outermost one.
+#[derive(Clone, Default)]
+pub struct GrpcCustomLayers {
+    layers: Vec<GrpcLayerFn>,
+}
+
+impl GrpcCustomLayers {
+    pub(crate) fn add<L, ResBody>(&mut self, layer: L)
+    where
+        L: Layer<GrpcBoxService> + Send + Sync + 'static,
+        L::Service: Service<Request<Body>, Response = Response<ResBody>> + Clone + Send + 'static,
+        <L::Service as Service<Request<Body>>>::Future: Send + 'static,
+        <L::Service as Service<Request<Body>>>::Error: Into<GrpcBoxError>,
+        ResBody: my_grpc_extensions::hyper::body::Body<Data = Bytes> + Send + 'static,
+        ResBody::Error: Into<GrpcBoxError>,
+    {
+        self.layers.push(Arc::new(move |service| {
+            let service = layer
+                .layer(service)
+                .map_response(|response| response.map(Body::new))
+                .map_err(Into::into);
+            GrpcBoxService::new(service)
+        }));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+}
+
+impl<S> Layer<S> for GrpcCustomLayers
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Into<GrpcBoxError>,
+{
+    type Service = GrpcBoxService;
+
+    fn layer(&self, service: S) -> Self::Service {
+        let mut service = GrpcBoxService::new(service.map_err(Into::into));
+
+        for layer in self.layers.iter().rev() {
+            service = layer(service);
+        }
+
+        service
+    }
+}
@@ -1,45 +1,112 @@
 use std::{
     convert::Infallible,
     net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
 };
 
+use my_grpc_extensions::hyper;
 use my_grpc_extensions::tonic::{
     body::Body,
     codegen::{http::Request, Service},
     server::NamedService,
-    transport::{server::Router, Server},
+    service::{Interceptor, InterceptorLayer},
+    transport::{
+        server::{Router, TcpIncoming},
+        Server,
+    },
 };
 
 use my_logger::LogEventCtx;
+use tokio::task::JoinHandle;
 
-use crate::GrpcMetricsMiddlewareLayer;
+use crate::{
+    GracefulShutdown, GrpcBoxError, GrpcBoxService, GrpcCompression, GrpcCustomLayers,
+    GrpcHealthStatus, GrpcMetricsMiddlewareLayer, GrpcServerLayer, GrpcServerOptions, StartupError,
+};
 
 use crate::IntoGrpcServer;
 
 const DEFAULT_GRPC_PORT: u16 = 8888;
+
+const GRPC_HEALTH_SERVICE_NAME: &str = "grpc.health.v1.Health";
+const GRPC_REFLECTION_SERVICE_NAMES: [&str; 2] = [
+    "grpc.reflection.v1.ServerReflection",
+    "grpc.reflection.v1alpha.ServerReflection",
+];
+
+// Routers are built on bind, so layers and options added after a service still apply to it.
+type GrpcServiceRoute = Box<
+    dyn Fn(&mut Server<GrpcServerLayer>, Option<Router<GrpcServerLayer>>) -> Router<GrpcServerLayer>
+        + Send
+        + Sync,
+>;
+
+type GrpcRouterBuilder =
+    Box<dyn Fn(&mut Server<GrpcServerLayer>) -> Router<GrpcServerLayer> + Send + Sync>;
+
+// Services added with `add_service` are created on bind with the options set by then.
+type GrpcServiceFactory = Box<dyn FnOnce(&GrpcServerOptions) -> GrpcServiceRoute + Send + Sync>;
+
+// Generated tonic servers have no common trait for these settings.
+macro_rules! apply_message_options {
+    ($server:expr, $options:expr) => {{
+        let mut server = $server;
+
+        if let Some(limit) = $options.max_decoding_message_size {
+            server = server.max_decoding_message_size(limit);
+        }
+
+        if let Some(limit) = $options.max_encoding_message_size {
+            server = server.max_encoding_message_size(limit);
+        }
+
+        for encoding in $options.get_compression_encodings() {
+            server = server.accept_compressed(encoding).send_compressed(encoding);
+        }
+
+        server
+    }};
+}
+
 pub struct GrpcServerBuilder {
-    server: Option<
-        Router<
-            tower::layer::util::Stack<
-                tower::layer::util::Stack<GrpcMetricsMiddlewareLayer, tower::layer::util::Identity>,
-                tower::layer::util::Identity,
-            >,
-        >,
-    >,
+    service_factories: Vec<GrpcServiceFactory>,
+    services: Vec<GrpcServiceRoute>,
+    raw_service_names: Vec<&'static str>,
+    router_builder: Option<GrpcRouterBuilder>,
+
+    server: Option<Router<GrpcServerLayer>>,
 
     #[cfg(unix)]
-    server_unix_socket: Option<
-        Router<
-            tower::layer::util::Stack<
-                tower::layer::util::Stack<GrpcMetricsMiddlewareLayer, tower::layer::util::Identity>,
-                tower::layer::util::Identity,
-            >,
-        >,
-    >,
+    server_unix_socket: Option<Router<GrpcServerLayer>>,
     #[cfg(unix)]
-    mode: super::UnixSocketMode,
+    unix_socket_config: super::UnixSocketConfig,
+
+    #[cfg(unix)]
+    unix_socket_listener: Option<(String, tokio::net::UnixListener)>,
+    #[cfg(unix)]
+    unix_socket_path: Option<String>,
 
     listen_address: Option<SocketAddr>,
+    tcp_listener: Option<(SocketAddr, TcpIncoming)>,
+
+    running_servers: Vec<JoinHandle<()>>,
+
+    service_names: Vec<&'static str>,
+    health_enabled: bool,
+    health_status: Option<Arc<GrpcHealthStatus>>,
+    mirror_health_checks: bool,
+
+    reflection_enabled: bool,
+    file_descriptor_sets: Vec<&'static [u8]>,
+
+    custom_layers: GrpcCustomLayers,
+    options: GrpcServerOptions,
+
+    #[cfg(feature = "with-tls")]
+    tls_settings: Option<crate::TlsSettings>,
+    #[cfg(feature = "with-tls")]
+    tls_config: Option<Arc<crate::ReloadableTlsConfig>>,
 }
 
 impl Default for GrpcServerBuilder {
@@ -51,13 +118,131 @@ impl Default for GrpcServerBuilder {
 impl GrpcServerBuilder {
     pub fn new() -> Self {
         Self {
+            service_factories: vec![],
+            services: vec![],
+            raw_service_names: vec![],
+            router_builder: None,
             server: None,
             listen_address: None,
+            tcp_listener: None,
+            running_servers: vec![],
+            service_names: vec![],
+            health_enabled: false,
+            health_status: None,
+            mirror_health_checks: false,
+            reflection_enabled: false,
+            file_descriptor_sets: vec![],
+            custom_layers: GrpcCustomLayers::default(),
+            options: GrpcServerOptions::default(),
+            #[cfg(feature = "with-tls")]
+            tls_settings: None,
+            #[cfg(feature = "with-tls")]
+            tls_config: None,
             #[cfg(unix)]
             server_unix_socket: None,
             #[cfg(unix)]
-            mode: super::UnixSocketMode::default(),
+            unix_socket_listener: None,
+            #[cfg(unix)]
+            unix_socket_path: None,
+            #[cfg(unix)]
+            unix_socket_config: super::UnixSocketConfig::default(),
+        }
+    }
+
+    fn create_server(&self) -> Server<GrpcServerLayer> {
+        let mut server = Server::builder();
+
+        if let Some(interval) = self.options.get_http2_keepalive_interval() {
+            server = server.http2_keepalive_interval(Some(interval));
+        }
+
+        if let Some(timeout) = self.options.get_http2_keepalive_timeout() {
+            server = server.http2_keepalive_timeout(Some(timeout));
+        }
+
+        if let Some(limit) = self.options.concurrency_limit_per_connection {
+            server = server.concurrency_limit_per_connection(limit);
+        }
+
+        if let Some(timeout) = self.options.get_request_timeout() {
+            server = server.timeout(timeout);
         }
+
+        server
+            .layer(GrpcMetricsMiddlewareLayer::new(self.get_known_services()))
+            .layer(self.custom_layers.clone())
+    }
+
+    /// Replaces all transport options, e.g. with the ones from the settings model.
+    /// Options apply to both TCP and unix socket listeners and can be set until the server binds.
+    pub fn set_options(&mut self, options: GrpcServerOptions) -> &mut Self {
+        options.check_compression();
+        self.options = options;
+        self
+    }
+
+    pub fn set_max_decoding_message_size(&mut self, limit: usize) -> &mut Self {
+        self.options.max_decoding_message_size = Some(limit);
+        self
+    }
+
+    pub fn set_max_encoding_message_size(&mut self, limit: usize) -> &mut Self {
+        self.options.max_encoding_message_size = Some(limit);
+        self
+    }
+
+    pub fn set_http2_keepalive(&mut self, interval: Duration, timeout: Duration) -> &mut Self {
+        self.options.http2_keepalive_interval_ms = Some(interval.as_millis() as u64);
+        self.options.http2_keepalive_timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    pub fn set_concurrency_limit_per_connection(&mut self, limit: usize) -> &mut Self {
+        self.options.concurrency_limit_per_connection = Some(limit);
+        self
+    }
+
+    pub fn set_request_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.options.request_timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    pub fn set_tcp_nodelay(&mut self, enabled: bool) -> &mut Self {
+        self.options.tcp_nodelay = Some(enabled);
+        self
+    }
+
+    /// Services accept requests compressed with `compression` and compress responses for clients that support it.
+    pub fn enable_compression(&mut self, compression: GrpcCompression) -> &mut Self {
+        if !self.options.compression.contains(&compression) {
+            self.options.compression.push(compression);
+        }
+        self.options.check_compression();
+        self
+    }
+
+    /// Adds a tower layer in front of every gRPC service on both TCP and unix socket listeners,
+    /// the health and reflection services included. The layer added first is the outermost one.
+    pub fn add_layer<L, ResBody>(&mut self, layer: L) -> &mut Self
+    where
+        L: tower::Layer<GrpcBoxService> + Send + Sync + 'static,
+        L::Service:
+            Service<Request<Body>, Response = hyper::Response<ResBody>> + Clone + Send + 'static,
+        <L::Service as Service<Request<Body>>>::Future: Send + 'static,
+        <L::Service as Service<Request<Body>>>::Error: Into<GrpcBoxError>,
+        ResBody: hyper::body::Body<Data = hyper::body::Bytes> + Send + 'static,
+        ResBody::Error: Into<GrpcBoxError>,
+    {
+        self.custom_layers.add(layer);
+        self
+    }
+
+    /// Adds a tonic interceptor in front of every gRPC service. Same rules as `add_layer`.
+    pub fn add_interceptor(
+        &mut self,
+        interceptor: impl Interceptor + Clone + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.add_layer(InterceptorLayer::new(interceptor))
     }
 
     pub fn update_listen_endpoint(&mut self, ip: IpAddr, port: u16) {
@@ -66,12 +251,24 @@ impl GrpcServerBuilder {
 
     pub fn add_service<S>(&mut self, svc: S)
     where
-        S: IntoGrpcServer,
-        <S::GrpcServer as my_grpc_extensions::tonic::codegen::Service<Request<Body>>>::Future: Send + 'static,
+        S: IntoGrpcServer + Send + Sync + 'static,
+        <S::GrpcServer as my_grpc_extensions::tonic::codegen::Service<Request<Body>>>::Future:
+            Send + 'static,
     {
-        self.add_grpc_service(svc.into_grpc_server());
+        if let Some(file_descriptor_set) = S::FILE_DESCRIPTOR_SET {
+            self.file_descriptor_sets.push(file_descriptor_set);
+        }
+
+        self.service_names
+            .push(<S::GrpcServer as NamedService>::NAME);
+        self.service_factories.push(Box::new(move |options| {
+            get_service_route(svc.into_grpc_server_with_options(options))
+        }));
     }
 
+    /// Adds the service as it is: message size limits and compression can not be applied to it,
+    /// so the server fails to bind if they are set. Use `add_service` for services generated by
+    /// `generate_grpc_service!` or configure the tonic server before adding it.
     pub fn add_grpc_service<S>(&mut self, svc: S)
     where
         S: Service<
@@ -85,109 +282,365 @@ impl GrpcServerBuilder {
             + 'static,
         S::Future: Send + 'static,
     {
-        #[cfg(unix)]
-        if self.mode.unix_socket_enabled() {
-            match self.server_unix_socket.take() {
-                Some(server_unix_socket) => {
-                    let server_unix_socket = server_unix_socket.add_service(svc.clone());
-                    self.server_unix_socket = Some(server_unix_socket);
-                }
-                None => {
-                    let layer = tower::ServiceBuilder::new()
-                        .layer(GrpcMetricsMiddlewareLayer)
-                        .into_inner();
-
-                    let server_unix_socket =
-                        Server::builder().layer(layer).add_service(svc.clone());
-
-                    self.server_unix_socket = Some(server_unix_socket);
-                }
-            };
-        }
+        self.service_names.push(S::NAME);
+        self.raw_service_names.push(S::NAME);
+        self.services.push(get_service_route(svc));
+    }
 
-        #[cfg(unix)]
-        let tcp_enabled = self.mode.tcp_enabled();
-        #[cfg(not(unix))]
-        let tcp_enabled = true;
+    /// Health service gets the same message size limits and compression as `add_service` services.
+    pub fn enable_health_service(&mut self) -> &mut Self {
+        self.health_enabled = true;
+        self
+    }
 
-        if tcp_enabled {
-            match self.server.take() {
-                Some(server) => {
-                    let server = server.add_service(svc);
-                    self.server = Some(server);
-                }
-                None => {
-                    let layer = tower::ServiceBuilder::new()
-                        .layer(GrpcMetricsMiddlewareLayer)
-                        .into_inner();
-
-                    let server = Server::builder().layer(layer).add_service(svc);
-
-                    self.server = Some(server);
-                }
-            };
+    /// Same as `enable_health_service`, but the service also reports NOT_SERVING while any
+    /// critical health check registered on the ServiceContext fails.
+    pub fn enable_health_service_with_health_checks(&mut self) -> &mut Self {
+        self.mirror_health_checks = true;
+        self.enable_health_service()
+    }
+
+    /// Serves gRPC reflection v1 and v1alpha. Descriptors of services added with `add_service`
+    /// are registered automatically; `file_descriptor_sets` adds the rest.
+    pub fn enable_reflection(&mut self, file_descriptor_sets: &[&'static [u8]]) -> &mut Self {
+        self.reflection_enabled = true;
+        self.file_descriptor_sets
+            .extend_from_slice(file_descriptor_sets);
+        self
+    }
+
+    /// Serves the TCP listener over TLS. With `client_ca_path` clients must present a certificate
+    /// signed by that CA. Certificates are reloaded when the files change; unix socket stays plaintext.
+    #[cfg(feature = "with-tls")]
+    pub fn set_tls(
+        &mut self,
+        cert_path: &str,
+        key_path: &str,
+        client_ca_path: Option<&str>,
+    ) -> &mut Self {
+        self.set_tls_settings(crate::TlsSettings::new(cert_path, key_path, client_ca_path))
+    }
+
+    /// Like `set_tls`: applies to the TCP listener only, the unix socket stays plaintext.
+    #[cfg(feature = "with-tls")]
+    pub fn set_tls_settings(&mut self, tls_settings: crate::TlsSettings) -> &mut Self {
+        self.tls_settings = Some(tls_settings);
+        self
+    }
+
+    #[cfg(feature = "with-tls")]
+    pub fn get_tls_config(&self) -> Option<Arc<crate::ReloadableTlsConfig>> {
+        self.tls_config.clone()
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn set_unix_socket_config(&mut self, unix_socket_config: super::UnixSocketConfig) {
+        self.unix_socket_config = unix_socket_config;
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn remove_unix_socket(&self) {
+        if let Some(unix_socket_path) = self.unix_socket_path.as_ref() {
+            self.unix_socket_config
+                .remove_socket(unix_socket_path.as_str());
         }
     }
 
+    pub fn get_health_status(&self) -> Option<Arc<GrpcHealthStatus>> {
+        self.health_status.clone()
+    }
+
+    pub fn health_checks_are_mirrored(&self) -> bool {
+        self.mirror_health_checks
+    }
+
+    /// Metrics of services added this way are recorded with `unknown` service and method labels.
     #[deprecated(note = "Please use add_grpc_service several times")]
     pub fn add_grpc_services(
         &mut self,
-        add_function: impl Fn(
-            &mut Server<
-                tower::layer::util::Stack<
-                    tower::layer::util::Stack<
-                        GrpcMetricsMiddlewareLayer,
-                        tower::layer::util::Identity,
-                    >,
-                    tower::layer::util::Identity,
-                >,
-            >,
-        ) -> Router<
-            tower::layer::util::Stack<
-                tower::layer::util::Stack<GrpcMetricsMiddlewareLayer, tower::layer::util::Identity>,
-                tower::layer::util::Identity,
-            >,
-        >,
+        add_function: impl Fn(&mut Server<GrpcServerLayer>) -> Router<GrpcServerLayer>
+            + Send
+            + Sync
+            + 'static,
     ) {
-        let layer = tower::ServiceBuilder::new()
-            .layer(GrpcMetricsMiddlewareLayer)
-            .into_inner();
+        self.router_builder = Some(Box::new(add_function));
+    }
+
+    fn get_known_services(&self) -> Vec<&'static str> {
+        let mut result = self.service_names.clone();
+
+        if self.health_enabled {
+            result.push(GRPC_HEALTH_SERVICE_NAME);
+        }
+
+        if self.reflection_enabled {
+            result.extend(GRPC_REFLECTION_SERVICE_NAMES);
+        }
+
+        result
+    }
+
+    fn build_router(&self) -> Option<Router<GrpcServerLayer>> {
+        let mut server = self.create_server();
+
+        let mut router = self
+            .router_builder
+            .as_ref()
+            .map(|router_builder| router_builder(&mut server));
 
-        let mut server = Server::builder().layer(layer);
+        for service in self.services.iter() {
+            router = Some(service(&mut server, router));
+        }
+
+        router
+    }
 
-        let router = add_function(&mut server);
+    /// Listeners bound before an error are released, so a failed startup leaves nothing bound.
+    pub fn bind(&mut self, app_name: &str) -> Result<(), StartupError> {
+        let result = self.bind_listeners(app_name);
 
-        self.server = Some(router);
+        if result.is_err() {
+            self.unbind();
+        }
+
+        result
     }
 
-    pub fn start(&mut self, app_name: &str) {
+    fn unbind(&mut self) {
+        self.tcp_listener = None;
+        #[cfg(feature = "with-tls")]
+        {
+            self.tls_config = None;
+        }
+
         #[cfg(unix)]
-        if let Some(grpc_server) = self.server_unix_socket.take() {
-            let unix_socket_name =
-                rust_extensions::file_utils::format_path(format!("~/grpc/{}", app_name));
+        if let Some((unix_socket_name, _)) = self.unix_socket_listener.take() {
+            let _ = std::fs::remove_file(unix_socket_name.as_str());
+            self.unix_socket_path = None;
+        }
+    }
+
+    fn bind_listeners(&mut self, app_name: &str) -> Result<(), StartupError> {
+        if self.reflection_enabled {
+            self.add_reflection_services()?;
+        }
+
+        self.check_raw_services_options();
+
+        for service_factory in self.service_factories.drain(..) {
+            self.services.push(service_factory(&self.options));
+        }
+
+        // Health and reflection services are not in `service_names`: the health service reports only user services.
+        if self.health_enabled && self.health_status.is_none() {
+            let (health_reporter, health_server) = tonic_health::server::health_reporter();
+            self.services.push(get_service_route(apply_message_options!(
+                health_server,
+                self.options
+            )));
+            self.health_status = Some(Arc::new(GrpcHealthStatus::new(
+                health_reporter,
+                self.service_names.clone(),
+            )));
+        }
+
+        #[cfg(unix)]
+        if self.unix_socket_config.mode.unix_socket_enabled() {
+            self.server_unix_socket = self.build_router();
+        }
+
+        #[cfg(unix)]
+        if self.unix_socket_config.mode.tcp_enabled() {
+            self.server = self.build_router();
+        }
+
+        #[cfg(not(unix))]
+        {
+            self.server = self.build_router();
+        }
 
-            start_grpc_server_as_unix_socket(grpc_server, unix_socket_name.to_string());
+        #[cfg(unix)]
+        if self.server_unix_socket.is_some() {
+            let unix_socket_name = self.unix_socket_config.get_socket_path("grpc", app_name);
+
+            let listener = self
+                .unix_socket_config
+                .prepare_socket_path(unix_socket_name.as_str())
+                .and_then(|_| tokio::net::UnixListener::bind(unix_socket_name.as_str()))
+                .and_then(|listener| {
+                    self.unix_socket_config
+                        .apply_permissions(unix_socket_name.as_str())?;
+                    Ok(listener)
+                })
+                .map_err(|error| StartupError::UnixSocketBind {
+                    listener: "grpc",
+                    path: unix_socket_name.clone(),
+                    error,
+                })?;
+
+            self.unix_socket_path = Some(unix_socket_name.clone());
+            self.unix_socket_listener = Some((unix_socket_name, listener));
         }
 
+        #[cfg(not(unix))]
+        let _ = app_name;
+
         #[cfg(unix)]
-        let tcp_enabled = self.mode.tcp_enabled();
+        let tcp_enabled = self.unix_socket_config.mode.tcp_enabled();
         #[cfg(not(unix))]
         let tcp_enabled = true;
 
-        if tcp_enabled {
-            if let Some(grpc_server) = self.server.take() {
-                let grpc_addr = if let Some(taken) = self.listen_address {
-                    taken
-                } else {
-                    let grpc_port = get_grpc_port();
-                    SocketAddr::new(crate::consts::get_default_ip_address(), grpc_port)
-                };
-                start_grpc_server(grpc_server, grpc_addr);
+        if tcp_enabled && self.server.is_some() {
+            let grpc_addr = if let Some(taken) = self.listen_address {
+                taken
+            } else {
+                let grpc_port = get_grpc_port();
+                SocketAddr::new(crate::consts::get_default_ip_address(), grpc_port)
+            };
+
+            let listener = TcpIncoming::bind(grpc_addr)
+                .map_err(|error| StartupError::TcpBind {
+                    listener: "grpc",
+                    address: grpc_addr,
+                    error,
+                })?
+                .with_nodelay(Some(self.options.get_tcp_nodelay()));
+
+            self.tcp_listener = Some((grpc_addr, listener));
+
+            #[cfg(feature = "with-tls")]
+            if let Some(tls_settings) = self.tls_settings.clone() {
+                let tls_config =
+                    crate::ReloadableTlsConfig::load("grpc", tls_settings, vec![b"h2".to_vec()])?;
+                self.tls_config = Some(Arc::new(tls_config));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_reflection_services(&mut self) -> Result<(), StartupError> {
+        let mut file_descriptor_sets = self.file_descriptor_sets.clone();
+        if self.health_enabled {
+            file_descriptor_sets.push(tonic_health::pb::FILE_DESCRIPTOR_SET);
+        }
+
+        let mut v1 = tonic_reflection::server::Builder::configure();
+        let mut v1alpha = tonic_reflection::server::Builder::configure();
+        for file_descriptor_set in file_descriptor_sets {
+            v1 = v1.register_encoded_file_descriptor_set(file_descriptor_set);
+            v1alpha = v1alpha.register_encoded_file_descriptor_set(file_descriptor_set);
+        }
+
+        let v1 = v1
+            .build_v1()
+            .map_err(|err| StartupError::GrpcReflection(format!("{:?}", err)))?;
+        let v1alpha = v1alpha
+            .build_v1alpha()
+            .map_err(|err| StartupError::GrpcReflection(format!("{:?}", err)))?;
+
+        self.services
+            .push(get_service_route(apply_message_options!(v1, self.options)));
+        self.services.push(get_service_route(apply_message_options!(
+            v1alpha,
+            self.options
+        )));
+
+        Ok(())
+    }
+
+    fn check_raw_services_options(&self) {
+        if self.raw_service_names.is_empty() {
+            return;
+        }
+
+        if self.options.max_decoding_message_size.is_some()
+            || self.options.max_encoding_message_size.is_some()
+            || !self.options.compression.is_empty()
+        {
+            panic!(
+                "GRPC message size limits and compression can not be applied to services added with add_grpc_service: {:?}. Use add_service or configure them on the services",
+                self.raw_service_names
+            );
+        }
+    }
+
+    pub fn start(&mut self, shutdown: &GracefulShutdown) {
+        #[cfg(unix)]
+        if let Some((unix_socket_name, listener)) = self.unix_socket_listener.take() {
+            let grpc_server = self
+                .server_unix_socket
+                .take()
+                .expect("GRPC unix socket server must be configured before start");
+
+            let join_handle =
+                start_grpc_server_as_unix_socket(grpc_server, unix_socket_name, listener, shutdown);
+            self.running_servers.push(join_handle);
+        }
+
+        if let Some((grpc_addr, listener)) = self.tcp_listener.take() {
+            let grpc_server = self
+                .server
+                .take()
+                .expect("GRPC server must be configured before start");
+
+            #[cfg(feature = "with-tls")]
+            if let Some(tls_config) = self.tls_config.clone() {
+                let join_handle = start_grpc_server_with_tls(
+                    grpc_server,
+                    grpc_addr,
+                    listener,
+                    tls_config,
+                    shutdown,
+                );
+                self.running_servers.push(join_handle);
+                return;
+            }
+
+            let join_handle = start_grpc_server(grpc_server, grpc_addr, listener, shutdown);
+            self.running_servers.push(join_handle);
+        }
+    }
+
+    pub async fn wait_until_stopped(&mut self, drain_timeout: Duration) {
+        for mut join_handle in self.running_servers.drain(..) {
+            if tokio::time::timeout(drain_timeout, &mut join_handle)
+                .await
+                .is_err()
+            {
+                my_logger::LOGGER.write_warning(
+                    "Stopping GRPC Server".to_string(),
+                    format!(
+                        "GRPC server did not drain in-flight requests within {:?}. Aborting",
+                        drain_timeout
+                    ),
+                    LogEventCtx::new(),
+                );
+                join_handle.abort();
             }
         }
     }
 }
 
+fn get_service_route<S>(svc: S) -> GrpcServiceRoute
+where
+    S: Service<
+            Request<Body>,
+            Response = my_grpc_extensions::hyper::Response<Body>,
+            Error = Infallible,
+        > + NamedService
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    S::Future: Send + 'static,
+{
+    Box::new(move |server, router| match router {
+        Some(router) => router.add_service(svc.clone()),
+        None => server.add_service(svc.clone()),
+    })
+}
+
 fn get_grpc_port() -> u16 {
     if let Ok(port) = std::env::var("GRPC_PORT") {
         match port.as_str().parse::<u16>() {
@@ -200,47 +653,105 @@ fn get_grpc_port() -> u16 {
 }
 
 fn start_grpc_server(
-    server: Router<
-        tower::layer::util::Stack<
-            tower::layer::util::Stack<GrpcMetricsMiddlewareLayer, tower::layer::util::Identity>,
-            tower::layer::util::Identity,
-        >,
-    >,
+    server: Router<GrpcServerLayer>,
     grpc_addr: SocketAddr,
-) {
+    listener: TcpIncoming,
+    shutdown: &GracefulShutdown,
+) -> JoinHandle<()> {
     my_logger::LOGGER.write_info(
         "Starting GRPC Server".to_string(),
         format!("GRPC server starts at: {:?}", grpc_addr),
         LogEventCtx::new(),
     );
 
+    let shutdown_signal = shutdown.wait();
+
     tokio::spawn(async move {
-        server.serve(grpc_addr).await.unwrap();
-    });
+        if let Err(err) = server
+            .serve_with_incoming_shutdown(listener, shutdown_signal)
+            .await
+        {
+            my_logger::LOGGER.write_fatal_error(
+                "GRPC Server".to_string(),
+                format!(
+                    "GRPC server at {:?} stopped with error: {:?}",
+                    grpc_addr, err
+                ),
+                LogEventCtx::new(),
+            );
+        }
+    })
+}
+
+#[cfg(feature = "with-tls")]
+fn start_grpc_server_with_tls(
+    server: Router<GrpcServerLayer>,
+    grpc_addr: SocketAddr,
+    listener: TcpIncoming,
+    tls_config: Arc<crate::ReloadableTlsConfig>,
+    shutdown: &GracefulShutdown,
+) -> JoinHandle<()> {
+    my_logger::LOGGER.write_info(
+        "Starting GRPC Server".to_string(),
+        format!(
+            "GRPC server starts at: {:?} with TLS. mTLS: {}",
+            grpc_addr,
+            tls_config.client_auth_enabled()
+        ),
+        LogEventCtx::new(),
+    );
+
+    let incoming = super::tls_incoming(listener, tls_config, shutdown);
+    let shutdown_signal = shutdown.wait();
+
+    tokio::spawn(async move {
+        if let Err(err) = server
+            .serve_with_incoming_shutdown(incoming, shutdown_signal)
+            .await
+        {
+            my_logger::LOGGER.write_fatal_error(
+                "GRPC Server".to_string(),
+                format!(
+                    "GRPC server at {:?} stopped with error: {:?}",
+                    grpc_addr, err
+                ),
+                LogEventCtx::new(),
+            );
+        }
+    })
 }
 
 #[cfg(unix)]
 fn start_grpc_server_as_unix_socket(
-    server: Router<
-        tower::layer::util::Stack<
-            tower::layer::util::Stack<GrpcMetricsMiddlewareLayer, tower::layer::util::Identity>,
-            tower::layer::util::Identity,
-        >,
-    >,
+    server: Router<GrpcServerLayer>,
     unix_socket_addr: String,
-) {
+    listener: tokio::net::UnixListener,
+    shutdown: &GracefulShutdown,
+) -> JoinHandle<()> {
     my_logger::LOGGER.write_info(
         "Starting GRPC Server".to_string(),
         format!("GRPC server starts at: {:?}", unix_socket_addr),
         LogEventCtx::new(),
     );
 
+    let shutdown_signal = shutdown.wait();
+
     tokio::spawn(async move {
-        let _ = tokio::fs::remove_file(unix_socket_addr.as_str()).await;
-        let uds = tokio::net::UnixListener::bind(unix_socket_addr).unwrap();
-        let uds_stream = tokio_stream::wrappers::UnixListenerStream::new(uds);
-        server.serve_with_incoming(uds_stream).await.unwrap();
-    });
+        let uds_stream = tokio_stream::wrappers::UnixListenerStream::new(listener);
+        if let Err(err) = server
+            .serve_with_incoming_shutdown(uds_stream, shutdown_signal)
+            .await
+        {
+            my_logger::LOGGER.write_fatal_error(
+                "GRPC Server".to_string(),
+                format!(
+                    "GRPC server at {:?} stopped with error: {:?}",
+                    unix_socket_addr, err
+                ),
+                LogEventCtx::new(),
+            );
+        }
+    })
 }
 
 /*
@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use my_grpc_extensions::tonic::codec::CompressionEncoding;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GrpcCompression {
+    /// Requires the `grpc-gzip` feature.
+    Gzip,
+    /// Requires the `grpc-zstd` feature.
+    Zstd,
+}
+
+impl GrpcCompression {
+    fn get_name(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    fn get_encoding(&self) -> Option<CompressionEncoding> {
+        match self {
+            #[cfg(feature = "grpc-gzip")]
+            Self::Gzip => Some(CompressionEncoding::Gzip),
+            #[cfg(feature = "grpc-zstd")]
+            Self::Zstd => Some(CompressionEncoding::Zstd),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+}
+
+/// Transport options of the SDK gRPC server. Can be a part of the settings model.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GrpcServerOptions {
+    pub max_decoding_message_size: Option<usize>,
+    pub max_encoding_message_size: Option<usize>,
+    pub http2_keepalive_interval_ms: Option<u64>,
+    pub http2_keepalive_timeout_ms: Option<u64>,
+    pub concurrency_limit_per_connection: Option<usize>,
+    pub request_timeout_ms: Option<u64>,
+    /// Default is `true`.
+    pub tcp_nodelay: Option<bool>,
+    pub compression: Vec<GrpcCompression>,
+}
+
+impl GrpcServerOptions {
+    pub fn get_http2_keepalive_interval(&self) -> Option<Duration> {
+        self.http2_keepalive_interval_ms.map(Duration::from_millis)
+    }
+
+    pub fn get_http2_keepalive_timeout(&self) -> Option<Duration> {
+        self.http2_keepalive_timeout_ms.map(Duration::from_millis)
+    }
+
+    pub fn get_request_timeout(&self) -> Option<Duration> {
+        self.request_timeout_ms.map(Duration::from_millis)
+    }
+
+    pub fn get_tcp_nodelay(&self) -> bool {
+        self.tcp_nodelay.unwrap_or(true)
+    }
+
+    /// Encodings every service accepts and responds with, when the client supports them.
+    pub fn get_compression_encodings(&self) -> Vec<CompressionEncoding> {
+        self.compression
+            .iter()
+            .filter_map(|itm| itm.get_encoding())
+            .collect()
+    }
+
+    pub(crate) fn check_compression(&self) {
+        for compression in self.compression.iter() {
+            if compression.get_encoding().is_none() {
+                panic!(
+                    "gRPC compression {} requires the grpc-{} feature of service-sdk",
+                    compression.get_name(),
+                    compression.get_name()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::io::IoSlice;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use my_grpc_extensions::tonic::transport::server::{Connected, TcpIncoming};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::StreamExt;
+
+use crate::{GracefulShutdown, ReloadableTlsConfig, TlsConnectInfo};
+
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct GrpcTlsStream {
+    inner: tokio_rustls::server::TlsStream<TcpStream>,
+}
+
+impl Connected for GrpcTlsStream {
+    type ConnectInfo = TlsConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        let (tcp_stream, connection) = self.inner.get_ref();
+
+        TlsConnectInfo {
+            local_addr: tcp_stream.local_addr().ok(),
+            remote_addr: tcp_stream.peer_addr().ok(),
+            peer_certificates: connection
+                .peer_certificates()
+                .map(|certs| Arc::new(certs.to_vec())),
+        }
+    }
+}
+
+impl AsyncRead for GrpcTlsStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for GrpcTlsStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Accepts TCP connections and runs TLS handshakes concurrently, so a slow client
+/// can not block other connections. Every handshake uses the latest reloaded certificates.
+pub(crate) fn tls_incoming(
+    mut tcp_incoming: TcpIncoming,
+    tls_config: Arc<ReloadableTlsConfig>,
+    shutdown: &GracefulShutdown,
+) -> ReceiverStream<std::io::Result<GrpcTlsStream>> {
+    let (sender, receiver) = tokio::sync::mpsc::channel(128);
+    let shutdown_signal = shutdown.wait();
+
+    tokio::spawn(async move {
+        tokio::pin!(shutdown_signal);
+
+        loop {
+            let tcp_stream = tokio::select! {
+                tcp_stream = tcp_incoming.next() => tcp_stream,
+                _ = &mut shutdown_signal => break,
+            };
+
+            let tcp_stream = match tcp_stream {
+                Some(Ok(tcp_stream)) => tcp_stream,
+                Some(Err(err)) => {
+                    if sender.send(Err(err)).await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                None => break,
+            };
+
+            let acceptor = tls_config.get_acceptor();
+            let sender = sender.clone();
+
+            tokio::spawn(async move {
+                let accepted =
+                    tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(tcp_stream)).await;
+
+                if let Ok(Ok(inner)) = accepted {
+                    let _ = sender.send(Ok(GrpcTlsStream { inner })).await;
+                }
+            });
+        }
+    });
+
+    ReceiverStream::new(receiver)
+}
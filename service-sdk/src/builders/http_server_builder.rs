@@ -1,8 +1,11 @@
 use std::{
-    net::{IpAddr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     sync::Arc,
+    time::{Duration, Instant},
 };
 
+use rust_extensions::AppStates;
+
 use is_alive_middleware::IsAliveMiddleware;
 use my_http_server::controllers::{
     swagger::SwaggerMiddleware,
@@ -10,13 +13,63 @@ use my_http_server::controllers::{
 };
 use my_http_server::{HttpServerMiddleware, MyHttpServer};
 
-use crate::{MetricsMiddleware, MetricsTechMiddleware};
+use crate::{
+    HealthChecks, HealthMiddleware, HttpInFlightRequests, HttpMetricsRoutes,
+    MetricsInFlightMiddleware, MetricsMiddleware, MetricsTechMiddleware, ReadinessChecks,
+    ReadinessMiddleware, StartupError, HEALTH_PATH, JSON_METRICS_PATH, METRICS_PATH,
+    READINESS_PATH,
+};
+
+pub const DEFAULT_HTTP_LISTENER: &str = "public";
+pub const UNIX_SOCKET_HTTP_LISTENER: &str = "unix-socket";
+pub const DEFAULT_HTTP_START_TIMEOUT: Duration = Duration::from_secs(5);
+const LISTENING_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Endpoints the SDK adds to a listener by itself.
+#[derive(Debug, Clone, Copy)]
+pub struct HttpBuiltIns {
+    pub is_alive: bool,
+    pub readiness: bool,
+    pub health: bool,
+    pub metrics: bool,
+    pub swagger: bool,
+}
+
+impl HttpBuiltIns {
+    pub fn all() -> Self {
+        Self {
+            is_alive: true,
+            readiness: true,
+            health: true,
+            metrics: true,
+            swagger: true,
+        }
+    }
+
+    pub fn none() -> Self {
+        Self {
+            is_alive: false,
+            readiness: false,
+            health: false,
+            metrics: false,
+            swagger: false,
+        }
+    }
+}
+
+impl Default for HttpBuiltIns {
+    fn default() -> Self {
+        Self::all()
+    }
+}
 
 #[derive(Default)]
 pub struct HttpServerConfig {
     auth_middleware: Option<Arc<dyn HttpServerMiddleware + Send + Sync + 'static>>,
     custom_middlewares: Vec<Arc<dyn HttpServerMiddleware + Send + Sync + 'static>>,
     controllers: Option<ControllersMiddleware>,
+    built_ins: HttpBuiltIns,
+    metrics_routes: Arc<HttpMetricsRoutes>,
 }
 
 impl HttpServerConfig {
@@ -62,6 +115,7 @@ impl HttpServerConfig {
         if self.controllers.is_none() {
             self.controllers = Some(ControllersMiddleware::new(None, None));
         }
+        self.metrics_routes.register_route(action.get_route());
         self.controllers
             .as_mut()
             .unwrap()
@@ -77,6 +131,7 @@ impl HttpServerConfig {
         if self.controllers.is_none() {
             self.controllers = Some(ControllersMiddleware::new(None, None));
         }
+        self.metrics_routes.register_route(action.get_route());
         self.controllers
             .as_mut()
             .unwrap()
@@ -92,6 +147,7 @@ impl HttpServerConfig {
         if self.controllers.is_none() {
             self.controllers = Some(ControllersMiddleware::new(None, None));
         }
+        self.metrics_routes.register_route(action.get_route());
         self.controllers
             .as_mut()
             .unwrap()
@@ -105,6 +161,7 @@ impl HttpServerConfig {
         if self.controllers.is_none() {
             self.controllers = Some(ControllersMiddleware::new(None, None));
         }
+        self.metrics_routes.register_route(action.get_route());
         self.controllers
             .as_mut()
             .unwrap()
@@ -120,6 +177,7 @@ impl HttpServerConfig {
         if self.controllers.is_none() {
             self.controllers = Some(ControllersMiddleware::new(None, None));
         }
+        self.metrics_routes.register_route(action.get_route());
         self.controllers
             .as_mut()
             .unwrap()
@@ -131,28 +189,93 @@ impl HttpServerConfig {
         my_http_server: &mut MyHttpServer,
         app_name: &'static str,
         app_version: &'static str,
+        readiness_checks: &Arc<ReadinessChecks>,
+        health_checks: &Arc<HealthChecks>,
+        in_flight_requests: &Arc<HttpInFlightRequests>,
     ) {
-        let is_alive = IsAliveMiddleware::new(app_name, app_version);
-        my_http_server.add_middleware(Arc::new(is_alive));
-        my_http_server.add_middleware(Arc::new(MetricsMiddleware));
-        my_http_server.add_tech_middleware(Arc::new(MetricsTechMiddleware));
+        let mut middlewares: Vec<Arc<dyn HttpServerMiddleware + Send + Sync + 'static>> = vec![];
 
-        for middleware in self.custom_middlewares.drain(..) {
-            my_http_server.add_middleware(middleware);
+        if self.built_ins.is_alive {
+            self.metrics_routes.register_route("/api/isalive");
+            middlewares.push(Arc::new(IsAliveMiddleware::new(app_name, app_version)));
+        }
+        if self.built_ins.readiness {
+            self.metrics_routes.register_route(READINESS_PATH);
+            middlewares.push(Arc::new(ReadinessMiddleware::new(readiness_checks.clone())));
+        }
+        if self.built_ins.health {
+            self.metrics_routes.register_route(HEALTH_PATH);
+            middlewares.push(Arc::new(HealthMiddleware::new(health_checks.clone())));
         }
+        if self.built_ins.metrics {
+            self.metrics_routes.register_route(METRICS_PATH);
+            self.metrics_routes.register_route(JSON_METRICS_PATH);
+            middlewares.push(Arc::new(MetricsMiddleware));
+        }
+
+        middlewares.append(&mut self.custom_middlewares);
 
         if let Some(controllers) = self.controllers.take() {
             let controllers = Arc::new(controllers);
-            let swagger_middleware =
-                SwaggerMiddleware::new(controllers.clone(), app_name, app_version);
 
-            my_http_server.add_middleware(Arc::new(swagger_middleware));
+            if self.built_ins.swagger {
+                self.metrics_routes.register_route("/swagger/*");
+                let swagger_middleware =
+                    SwaggerMiddleware::new(controllers.clone(), app_name, app_version);
+                middlewares.push(Arc::new(swagger_middleware));
+            }
 
             if let Some(auth_middleware) = self.auth_middleware.take() {
-                my_http_server.add_middleware(auth_middleware);
+                middlewares.push(auth_middleware);
             }
-            my_http_server.add_middleware(controllers.clone());
+            middlewares.push(controllers);
         }
+
+        // Every middleware runs inside the in-flight one, so the request is counted until it is handled.
+        my_http_server.add_middleware(Arc::new(MetricsInFlightMiddleware::new(
+            self.metrics_routes.clone(),
+            in_flight_requests.clone(),
+            middlewares,
+        )));
+        my_http_server.add_tech_middleware(Arc::new(MetricsTechMiddleware::new(
+            self.metrics_routes.clone(),
+        )));
+    }
+}
+
+struct HttpListener {
+    name: &'static str,
+    listen_address: SocketAddr,
+    config: HttpServerConfig,
+}
+
+/// Address a started server listens on, checked by `wait_until_listening`.
+enum HttpListenerAddress {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    UnixSocket(String),
+}
+
+impl HttpListenerAddress {
+    async fn connect(&self) -> std::io::Result<()> {
+        match self {
+            Self::Tcp(address) => {
+                // A server listening on all interfaces is reached through the loopback of the same family.
+                let ip = match address.ip() {
+                    IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                    IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+                    ip => ip,
+                };
+
+                tokio::net::TcpStream::connect(SocketAddr::new(ip, address.port())).await?;
+            }
+            #[cfg(unix)]
+            Self::UnixSocket(path) => {
+                tokio::net::UnixStream::connect(path.as_str()).await?;
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -164,61 +287,173 @@ pub struct HttpServerBuilder {
 
     tcp: HttpServerConfig,
 
+    listeners: Vec<HttpListener>,
+    targets: Option<Vec<&'static str>>,
+
+    readiness_checks: Arc<ReadinessChecks>,
+    health_checks: Arc<HealthChecks>,
+
+    started_listeners: Vec<(&'static str, HttpListenerAddress)>,
+    in_flight_requests: Arc<HttpInFlightRequests>,
+
+    /// Kept whatever the mode is, so registrations survive `set_unix_socket_config`.
     #[cfg(unix)]
-    unix_socket: Option<HttpServerConfig>,
+    unix_socket: HttpServerConfig,
 
     #[cfg(unix)]
-    mode: super::UnixSocketMode,
+    unix_socket_config: super::UnixSocketConfig,
 }
 impl HttpServerBuilder {
     pub fn new(app_name: &'static str, app_version: &'static str) -> Self {
         #[cfg(unix)]
-        let mode = super::UnixSocketMode::default();
+        let unix_socket_config = super::UnixSocketConfig::default();
         Self {
             listen_address: SocketAddr::new(crate::consts::get_default_ip_address(), 8000),
             app_name,
             app_version,
             tcp: HttpServerConfig::default(),
+            listeners: vec![],
+            targets: None,
+            readiness_checks: Arc::new(ReadinessChecks::new()),
+            health_checks: Arc::new(HealthChecks::new()),
+            started_listeners: vec![],
+            in_flight_requests: Arc::new(HttpInFlightRequests::default()),
             #[cfg(unix)]
-            unix_socket: if mode.unix_socket_enabled() {
-                Some(HttpServerConfig::default())
-            } else {
-                None
-            },
+            unix_socket: HttpServerConfig::default(),
             #[cfg(unix)]
-            mode,
+            unix_socket_config,
         }
     }
 
-    pub fn set_authorization(&mut self, authorization: ControllersAuthorization) {
+    pub(crate) fn set_readiness_checks(&mut self, readiness_checks: Arc<ReadinessChecks>) {
+        self.readiness_checks = readiness_checks;
+    }
+
+    pub(crate) fn set_health_checks(&mut self, health_checks: Arc<HealthChecks>) {
+        self.health_checks = health_checks;
+    }
+
+    /// Adds a TCP listener. It serves only the built-ins until actions are registered on it with `with_listeners`.
+    /// It is started in unix-socket-only mode too.
+    pub fn add_listener(&mut self, name: &'static str, ip: IpAddr, port: u16) -> &mut Self {
+        if name == DEFAULT_HTTP_LISTENER
+            || name == UNIX_SOCKET_HTTP_LISTENER
+            || self.listeners.iter().any(|itm| itm.name == name)
+        {
+            panic!("HTTP listener {} is already registered", name);
+        }
+
+        self.listeners.push(HttpListener {
+            name,
+            listen_address: SocketAddr::new(ip, port),
+            config: HttpServerConfig::default(),
+        });
+        self
+    }
+
+    pub fn set_built_ins(&mut self, listener: &'static str, built_ins: HttpBuiltIns) -> &mut Self {
+        for config in self.get_configs(&[listener]) {
+            config.built_ins = built_ins;
+        }
+        self
+    }
+
+    /// Everything registered inside `configure` (actions, middlewares, authorization) goes only to `listeners`.
+    /// Outside of it registrations go to the `public` listener and the unix socket.
+    pub fn with_listeners(
+        &mut self,
+        listeners: &[&'static str],
+        configure: impl FnOnce(&mut Self),
+    ) -> &mut Self {
+        let prev_targets = self.targets.replace(listeners.to_vec());
+        configure(self);
+        self.targets = prev_targets;
+        self
+    }
+
+    fn get_configs(&mut self, names: &[&'static str]) -> Vec<&mut HttpServerConfig> {
+        for name in names {
+            let registered = *name == DEFAULT_HTTP_LISTENER
+                || *name == UNIX_SOCKET_HTTP_LISTENER
+                || self.listeners.iter().any(|itm| itm.name == *name);
+
+            if !registered {
+                panic!("HTTP listener {} is not registered", name);
+            }
+        }
+
+        let mut result = vec![];
+
+        if names.contains(&DEFAULT_HTTP_LISTENER) {
+            result.push(&mut self.tcp);
+        }
+
         #[cfg(unix)]
-        if let Some(unix_socket) = self.unix_socket.as_mut() {
-            unix_socket.set_authorization(authorization.clone());
+        if names.contains(&UNIX_SOCKET_HTTP_LISTENER) {
+            result.push(&mut self.unix_socket);
         }
 
-        self.tcp.set_authorization(authorization);
+        for listener in self.listeners.iter_mut() {
+            if names.contains(&listener.name) {
+                result.push(&mut listener.config);
+            }
+        }
+
+        result
+    }
+
+    fn get_targets(&mut self) -> Vec<&mut HttpServerConfig> {
+        let targets = self
+            .targets
+            .clone()
+            .unwrap_or_else(|| vec![DEFAULT_HTTP_LISTENER, UNIX_SOCKET_HTTP_LISTENER]);
+        self.get_configs(&targets)
+    }
+
+    pub fn set_authorization(&mut self, authorization: ControllersAuthorization) {
+        for config in self.get_targets() {
+            config.set_authorization(authorization.clone());
+        }
     }
 
     pub fn set_auth_error_factory(&mut self, value: impl AuthErrorFactory + Send + Sync + 'static) {
         let value = Arc::new(value);
-        #[cfg(unix)]
-        if let Some(unix_socket) = self.unix_socket.as_mut() {
-            unix_socket.set_auth_error_factory(value.clone());
+        for config in self.get_targets() {
+            config.set_auth_error_factory(value.clone());
         }
-
-        self.tcp.set_auth_error_factory(value);
     }
 
     pub fn register_custom_middleware(
         &mut self,
         middleware: Arc<dyn HttpServerMiddleware + Send + Sync + 'static>,
     ) {
-        #[cfg(unix)]
-        if let Some(unix_socket) = self.unix_socket.as_mut() {
-            unix_socket.register_custom_middleware(middleware.clone());
+        for config in self.get_targets() {
+            config.register_custom_middleware(middleware.clone());
         }
+    }
 
-        self.tcp.register_custom_middleware(middleware);
+    /// Route template for requests served by custom middlewares. Actions are registered automatically.
+    pub fn register_metrics_route(&mut self, template: &str) -> &mut Self {
+        for config in self.get_targets() {
+            config.metrics_routes.register_route(template);
+        }
+        self
+    }
+
+    /// Only these route templates are recorded by HTTP metrics.
+    pub fn set_metrics_allow_list(&mut self, routes: &[&str]) -> &mut Self {
+        for config in self.get_targets() {
+            config.metrics_routes.set_allow_list(routes);
+        }
+        self
+    }
+
+    /// These route templates (or `unmatched`) are excluded from HTTP metrics.
+    pub fn set_metrics_deny_list(&mut self, routes: &[&str]) -> &mut Self {
+        for config in self.get_targets() {
+            config.metrics_routes.set_deny_list(routes);
+        }
+        self
     }
 
     pub fn update_listen_endpoint(&mut self, ip: IpAddr, port: u16) {
@@ -229,12 +464,9 @@ impl HttpServerBuilder {
         &mut self,
         middleware: Arc<dyn HttpServerMiddleware + Send + Sync + 'static>,
     ) -> &mut Self {
-        #[cfg(unix)]
-        if let Some(unix_socket) = self.unix_socket.as_mut() {
-            unix_socket.add_auth_middleware(middleware.clone());
+        for config in self.get_targets() {
+            config.add_auth_middleware(middleware.clone());
         }
-
-        self.tcp.add_auth_middleware(middleware);
         self
     }
 
@@ -243,12 +475,9 @@ impl HttpServerBuilder {
         action: impl GetAction + Clone + HandleHttpRequest + GetDescription + Send + Sync + 'static,
     ) -> &mut Self {
         let action = Arc::new(action);
-        #[cfg(unix)]
-        if let Some(unix_socket) = self.unix_socket.as_mut() {
-            unix_socket.register_get_action(action.clone());
+        for config in self.get_targets() {
+            config.register_get_action(action.clone());
         }
-
-        self.tcp.register_get_action(action);
         self
     }
 
@@ -264,13 +493,10 @@ impl HttpServerBuilder {
             + 'static,
     ) -> &mut Self {
         let action = Arc::new(action);
-        #[cfg(unix)]
-        if let Some(unix_socket) = self.unix_socket.as_mut() {
-            unix_socket.register_post_action(action.clone());
+        for config in self.get_targets() {
+            config.register_post_action(action.clone());
         }
 
-        self.tcp.register_post_action(action);
-
         self
     }
 
@@ -279,12 +505,9 @@ impl HttpServerBuilder {
         action: impl PutAction + Clone + HandleHttpRequest + GetDescription + Send + Sync + 'static,
     ) -> &mut Self {
         let action = Arc::new(action);
-        #[cfg(unix)]
-        if let Some(unix_socket) = self.unix_socket.as_mut() {
-            unix_socket.register_put_action(action.clone());
+        for config in self.get_targets() {
+            config.register_put_action(action.clone());
         }
-
-        self.tcp.register_put_action(action);
         self
     }
 
@@ -293,12 +516,9 @@ impl HttpServerBuilder {
         action: impl DeleteAction + HandleHttpRequest + GetDescription + Send + Sync + 'static,
     ) -> &mut Self {
         let action = Arc::new(action);
-        #[cfg(unix)]
-        if let Some(unix_socket) = self.unix_socket.as_mut() {
-            unix_socket.register_delete_action(action.clone());
+        for config in self.get_targets() {
+            config.register_delete_action(action.clone());
         }
-
-        self.tcp.register_delete_action(action);
         self
     }
 
@@ -307,47 +527,284 @@ impl HttpServerBuilder {
         action: impl OptionsAction + HandleHttpRequest + GetDescription + Send + Sync + 'static,
     ) -> &mut Self {
         let action = Arc::new(action);
+        for config in self.get_targets() {
+            config.register_options_action(action.clone());
+        }
+        self
+    }
+
+    /// Changes only the mode and the path: actions registered for the unix socket are kept.
+    #[cfg(unix)]
+    pub(crate) fn set_unix_socket_config(&mut self, unix_socket_config: super::UnixSocketConfig) {
+        self.unix_socket_config = unix_socket_config;
+    }
+
+    #[cfg(unix)]
+    fn get_unix_socket_name(&self) -> String {
+        self.unix_socket_config
+            .get_socket_path("http", self.app_name)
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn remove_unix_socket(&self) {
+        if self.unix_socket_config.mode.unix_socket_enabled() {
+            self.unix_socket_config
+                .remove_socket(self.get_unix_socket_name().as_str());
+        }
+    }
+
+    fn tcp_enabled(&self) -> bool {
+        #[cfg(unix)]
+        {
+            self.unix_socket_config.mode.tcp_enabled()
+        }
+        #[cfg(not(unix))]
+        {
+            true
+        }
+    }
+
+    /// Checks that every listener can be bound, so bind errors are returned before the application reports it is started.
+    /// `my_http_server` binds the sockets itself once the servers start: an address taken in between
+    /// is reported by `wait_until_listening`.
+    pub(crate) fn bind(&self) -> Result<(), StartupError> {
         #[cfg(unix)]
-        if let Some(unix_socket) = self.unix_socket.as_mut() {
-            unix_socket.register_options_action(action.clone());
+        if self.unix_socket_config.mode.unix_socket_enabled() {
+            let unix_socket_name = self.get_unix_socket_name();
+            self.unix_socket_config
+                .prepare_socket_path(unix_socket_name.as_str())
+                .map_err(|error| StartupError::UnixSocketBind {
+                    listener: "http",
+                    path: unix_socket_name,
+                    error,
+                })?;
         }
 
-        self.tcp.register_options_action(action);
-        self
+        if self.tcp_enabled() {
+            check_tcp_bind("http", self.listen_address)?;
+        }
+
+        // `UNIX_SOCKET=ONLY` replaces only the public endpoint: named listeners are always served.
+        for listener in self.listeners.iter() {
+            check_tcp_bind(listener.name, listener.listen_address)?;
+        }
+
+        Ok(())
     }
 
+    /// Servers of every listener, not started. Panics when a listener can not be bound, see `bind`.
     pub fn build(&mut self) -> Vec<MyHttpServer> {
+        if let Err(err) = self.bind() {
+            panic!("{}", err);
+        }
+
+        self.build_servers()
+    }
+
+    fn build_servers(&mut self) -> Vec<MyHttpServer> {
         let mut result = vec![];
-        #[cfg(unix)]
-        if let Some(unix_socket) = self.unix_socket.as_mut() {
-            let unix_socket_name =
-                rust_extensions::file_utils::format_path(format!("~/http/{}", self.app_name));
+        self.started_listeners.clear();
 
-            let mut my_http_server = MyHttpServer::new_as_unix_socket(unix_socket_name.to_string());
+        #[cfg(unix)]
+        if self.unix_socket_config.mode.unix_socket_enabled() {
+            let unix_socket_name = self.get_unix_socket_name();
+            let mut my_http_server = MyHttpServer::new_as_unix_socket(unix_socket_name.clone());
 
-            unix_socket.build(
+            self.unix_socket.build(
                 &mut my_http_server,
                 self.app_name,
                 self.app_version,
+                &self.readiness_checks,
+                &self.health_checks,
+                &self.in_flight_requests,
             );
+
+            self.started_listeners.push((
+                UNIX_SOCKET_HTTP_LISTENER,
+                HttpListenerAddress::UnixSocket(unix_socket_name),
+            ));
             result.push(my_http_server);
         }
 
-        #[cfg(unix)]
-        let tcp_enabled = self.mode.tcp_enabled();
-        #[cfg(not(unix))]
-        let tcp_enabled = true;
-
-        if tcp_enabled {
+        if self.tcp_enabled() {
             let mut my_http_server = MyHttpServer::new(self.listen_address);
             self.tcp.build(
                 &mut my_http_server,
                 self.app_name,
                 self.app_version,
+                &self.readiness_checks,
+                &self.health_checks,
+                &self.in_flight_requests,
+            );
+
+            self.started_listeners.push((
+                DEFAULT_HTTP_LISTENER,
+                HttpListenerAddress::Tcp(self.listen_address),
+            ));
+            result.push(my_http_server);
+        }
+
+        for listener in self.listeners.iter_mut() {
+            let mut my_http_server = MyHttpServer::new(listener.listen_address);
+            listener.config.build(
+                &mut my_http_server,
+                self.app_name,
+                self.app_version,
+                &self.readiness_checks,
+                &self.health_checks,
+                &self.in_flight_requests,
             );
+
+            self.started_listeners.push((
+                listener.name,
+                HttpListenerAddress::Tcp(listener.listen_address),
+            ));
             result.push(my_http_server);
         }
 
         result
     }
+
+    /// Starts a server on every listener checked by `bind`.
+    pub(crate) fn start(&mut self, app_states: &Arc<AppStates>) -> Vec<MyHttpServer> {
+        let mut result = self.build_servers();
+
+        for my_http_server in result.iter_mut() {
+            my_http_server.start_auto(app_states.clone(), my_logger::LOGGER.clone());
+        }
+
+        result
+    }
+
+    /// Waits until every started server accepts connections and applies the unix socket permissions.
+    /// A server which could not bind its address fails startup here.
+    pub(crate) async fn wait_until_listening(&self, timeout: Duration) -> Result<(), StartupError> {
+        for (listener, address) in self.started_listeners.iter() {
+            let result = wait_until_accepting(address, timeout).await;
+
+            match address {
+                HttpListenerAddress::Tcp(address) => {
+                    result.map_err(|error| StartupError::TcpBind {
+                        listener: *listener,
+                        address: *address,
+                        error,
+                    })?;
+                }
+                #[cfg(unix)]
+                HttpListenerAddress::UnixSocket(path) => {
+                    result
+                        .and_then(|_| self.unix_socket_config.apply_permissions(path.as_str()))
+                        .map_err(|error| StartupError::UnixSocketBind {
+                            listener: "http",
+                            path: path.clone(),
+                            error,
+                        })?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Waits until the requests in flight are handled after shutdown is requested.
+    pub(crate) async fn wait_until_stopped(&self, drain_timeout: Duration) {
+        let drained =
+            tokio::time::timeout(drain_timeout, self.in_flight_requests.wait_until_empty()).await;
+
+        if drained.is_err() {
+            my_logger::LOGGER.write_warning(
+                "Stopping Http Server".to_string(),
+                format!(
+                    "HTTP server did not drain in-flight requests within {:?}",
+                    drain_timeout
+                ),
+                my_logger::LogEventCtx::new(),
+            );
+        }
+    }
+}
+
+fn check_tcp_bind(listener: &'static str, address: SocketAddr) -> Result<(), StartupError> {
+    std::net::TcpListener::bind(address)
+        .map(|_| ())
+        .map_err(|error| StartupError::TcpBind {
+            listener,
+            address,
+            error,
+        })
+}
+
+async fn wait_until_accepting(
+    address: &HttpListenerAddress,
+    timeout: Duration,
+) -> std::io::Result<()> {
+    let started = Instant::now();
+
+    loop {
+        match address.connect().await {
+            Ok(()) => return Ok(()),
+            Err(err) if started.elapsed() >= timeout => return Err(err),
+            Err(_) => tokio::time::sleep(LISTENING_CHECK_INTERVAL).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    fn get_builder() -> HttpServerBuilder {
+        let mut builder = HttpServerBuilder::new("sdk-test", "1.0.0");
+        #[cfg(unix)]
+        builder.set_unix_socket_config(crate::UnixSocketConfig {
+            mode: crate::UnixSocketMode::Disabled,
+            ..Default::default()
+        });
+        builder
+    }
+
+    #[test]
+    fn test_busy_port_returns_tcp_bind_error() {
+        let busy_listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let busy_address = busy_listener.local_addr().unwrap();
+
+        let mut builder = get_builder();
+        builder.update_listen_endpoint(busy_address.ip(), busy_address.port());
+
+        match builder.bind() {
+            Err(StartupError::TcpBind {
+                listener, address, ..
+            }) => {
+                assert_eq!(listener, "http");
+                assert_eq!(address, busy_address);
+            }
+            other => panic!("Expected TcpBind error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_busy_port_of_named_listener_returns_tcp_bind_error() {
+        let busy_listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let busy_address = busy_listener.local_addr().unwrap();
+
+        let mut builder = get_builder();
+        builder.update_listen_endpoint(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+        builder.add_listener("internal", busy_address.ip(), busy_address.port());
+
+        match builder.bind() {
+            Err(StartupError::TcpBind { listener, .. }) => assert_eq!(listener, "internal"),
+            other => panic!("Expected TcpBind error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_free_ports_are_bound() {
+        let mut builder = get_builder();
+        builder.update_listen_endpoint(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+        builder.add_listener("internal", IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+        assert!(builder.bind().is_ok());
+    }
 }
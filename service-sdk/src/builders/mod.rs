@@ -5,5 +5,23 @@ pub use http_server_builder::*;
 mod grpc_server_builder;
 #[cfg(feature = "grpc")]
 pub use grpc_server_builder::*;
+#[cfg(feature = "grpc")]
+mod grpc_health;
+#[cfg(feature = "grpc")]
+pub use grpc_health::*;
 mod unix_socket_enabled;
 pub use unix_socket_enabled::*;
+#[cfg(feature = "grpc")]
+mod grpc_layers;
+#[cfg(feature = "grpc")]
+pub use grpc_layers::*;
+#[cfg(all(feature = "grpc", feature = "with-tls"))]
+mod grpc_tls_incoming;
+#[cfg(all(feature = "grpc", feature = "with-tls"))]
+pub use grpc_tls_incoming::*;
+#[cfg(feature = "grpc")]
+mod grpc_server_options;
+#[cfg(feature = "grpc")]
+pub use grpc_server_options::*;
+mod unix_socket_config;
+pub use unix_socket_config::*;
@@ -0,0 +1,236 @@
+use serde::Deserialize;
+
+use super::UnixSocketMode;
+
+/// Unix socket listeners of the HTTP and gRPC servers. Every field falls back to env variables:
+/// `UNIX_SOCKET`, `UNIX_SOCKET_DIR`, `UNIX_SOCKET_FILE_MODE` (octal), `UNIX_SOCKET_UID`, `UNIX_SOCKET_GID`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct UnixSocketConfig {
+    pub mode: UnixSocketMode,
+    /// Sockets are created as `<base_dir>/http/<app>` and `<base_dir>/grpc/<app>`.
+    pub base_dir: String,
+    pub file_mode: Option<u32>,
+    pub owner_uid: Option<u32>,
+    pub owner_gid: Option<u32>,
+    pub remove_on_shutdown: bool,
+}
+
+impl Default for UnixSocketConfig {
+    fn default() -> Self {
+        Self {
+            mode: UnixSocketMode::default(),
+            base_dir: std::env::var("UNIX_SOCKET_DIR").unwrap_or_else(|_| "~".to_string()),
+            file_mode: std::env::var("UNIX_SOCKET_FILE_MODE")
+                .ok()
+                .and_then(|itm| u32::from_str_radix(itm.as_str(), 8).ok()),
+            owner_uid: std::env::var("UNIX_SOCKET_UID")
+                .ok()
+                .and_then(|itm| itm.parse().ok()),
+            owner_gid: std::env::var("UNIX_SOCKET_GID")
+                .ok()
+                .and_then(|itm| itm.parse().ok()),
+            remove_on_shutdown: true,
+        }
+    }
+}
+
+impl UnixSocketConfig {
+    pub fn get_socket_path(&self, server_kind: &str, app_name: &str) -> String {
+        let base_dir = self.base_dir.trim_end_matches('/');
+        rust_extensions::file_utils::format_path(format!(
+            "{}/{}/{}",
+            base_dir, server_kind, app_name
+        ))
+        .to_string()
+    }
+
+    /// Creates the parent directory and removes a socket file left by a process which is gone.
+    /// Fails if another process still accepts connections on the socket or if the path is not a socket.
+    #[cfg(unix)]
+    pub fn prepare_socket_path(&self, path: &str) -> std::io::Result<()> {
+        use std::os::unix::fs::FileTypeExt;
+
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let metadata = match std::fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        if !metadata.file_type().is_socket() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a unix socket", path),
+            ));
+        }
+
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AddrInUse,
+                format!("{} is used by another running process", path),
+            ));
+        }
+
+        my_logger::LOGGER.write_info(
+            "Unix Socket".to_string(),
+            format!("Removing stale unix socket {}", path),
+            my_logger::LogEventCtx::new(),
+        );
+        std::fs::remove_file(path)
+    }
+
+    #[cfg(unix)]
+    pub fn apply_permissions(&self, path: &str) -> std::io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let Some(file_mode) = self.file_mode {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(file_mode))?;
+        }
+
+        if self.owner_uid.is_some() || self.owner_gid.is_some() {
+            std::os::unix::fs::chown(path, self.owner_uid, self.owner_gid)?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    pub fn remove_socket(&self, path: &str) {
+        if self.remove_on_shutdown {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    use std::os::unix::net::UnixListener;
+
+    use super::*;
+
+    fn get_config() -> UnixSocketConfig {
+        UnixSocketConfig {
+            mode: UnixSocketMode::default(),
+            base_dir: "/var/run/sockets".to_string(),
+            file_mode: None,
+            owner_uid: None,
+            owner_gid: None,
+            remove_on_shutdown: true,
+        }
+    }
+
+    fn get_path(dir: &tempfile::TempDir, name: &str) -> String {
+        dir.path().join(name).to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_socket_path() {
+        let mut config = get_config();
+        assert_eq!(
+            config.get_socket_path("http", "orders"),
+            "/var/run/sockets/http/orders"
+        );
+
+        config.base_dir = "/var/run/sockets/".to_string();
+        assert_eq!(
+            config.get_socket_path("grpc", "orders"),
+            "/var/run/sockets/grpc/orders"
+        );
+    }
+
+    #[test]
+    fn test_parent_dir_is_created() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = get_path(&dir, "http/orders");
+
+        get_config().prepare_socket_path(&path).unwrap();
+
+        assert!(dir.path().join("http").is_dir());
+    }
+
+    #[test]
+    fn test_stale_socket_is_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = get_path(&dir, "orders");
+
+        // The listener is gone, its socket file is left.
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(std::path::Path::new(&path).exists());
+
+        get_config().prepare_socket_path(&path).unwrap();
+
+        assert!(!std::path::Path::new(&path).exists());
+        UnixListener::bind(&path).unwrap();
+    }
+
+    #[test]
+    fn test_live_socket_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = get_path(&dir, "orders");
+        let _listener = UnixListener::bind(&path).unwrap();
+
+        let err = get_config().prepare_socket_path(&path).unwrap_err();
+
+        assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+        assert!(std::path::Path::new(&path).exists());
+    }
+
+    #[test]
+    fn test_file_which_is_not_socket_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = get_path(&dir, "orders");
+        std::fs::write(&path, "data").unwrap();
+
+        let err = get_config().prepare_socket_path(&path).unwrap_err();
+
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+    }
+
+    #[test]
+    fn test_permissions_and_owner_are_applied() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = get_path(&dir, "orders");
+        let _listener = UnixListener::bind(&path).unwrap();
+
+        // Only root can give a file away: the test keeps the owner it has.
+        let metadata = std::fs::metadata(&path).unwrap();
+        let config = UnixSocketConfig {
+            file_mode: Some(0o660),
+            owner_uid: Some(metadata.uid()),
+            owner_gid: Some(metadata.gid()),
+            ..get_config()
+        };
+
+        config.apply_permissions(&path).unwrap();
+
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o660);
+        assert_eq!(metadata.uid(), config.owner_uid.unwrap());
+        assert_eq!(metadata.gid(), config.owner_gid.unwrap());
+    }
+
+    #[test]
+    fn test_socket_is_removed_on_shutdown_if_configured() {
+        let dir = tempfile::tempdir().unwrap();
+        let removed_path = get_path(&dir, "removed");
+        let kept_path = get_path(&dir, "kept");
+        drop(UnixListener::bind(&removed_path).unwrap());
+        drop(UnixListener::bind(&kept_path).unwrap());
+
+        get_config().remove_socket(&removed_path);
+        UnixSocketConfig {
+            remove_on_shutdown: false,
+            ..get_config()
+        }
+        .remove_socket(&kept_path);
+
+        assert!(!std::path::Path::new(&removed_path).exists());
+        assert!(std::path::Path::new(&kept_path).exists());
+    }
+}
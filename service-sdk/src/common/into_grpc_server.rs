@@ -17,5 +17,17 @@ pub trait IntoGrpcServer {
         + Sync
         + 'static;
 
+    /// Encoded `FileDescriptorSet` of the service. Served by the reflection service when enabled.
+    const FILE_DESCRIPTOR_SET: Option<&'static [u8]> = None;
+
     fn into_grpc_server(self) -> Self::GrpcServer;
+
+    /// Applies message size limits and compression of `options`. Generated by `generate_grpc_service!`.
+    fn into_grpc_server_with_options(self, options: &crate::GrpcServerOptions) -> Self::GrpcServer
+    where
+        Self: Sized,
+    {
+        let _ = options;
+        self.into_grpc_server()
+    }
 }
@@ -1,7 +1,9 @@
 mod service_info;
 pub use service_info::*;
+mod startup_error;
+pub use startup_error::*;
 
 #[cfg(feature = "grpc")]
 mod into_grpc_server;
 #[cfg(feature = "grpc")]
-pub use into_grpc_server::*;
\ No newline at end of file
+pub use into_grpc_server::*;
@@ -0,0 +1,77 @@
+use std::net::SocketAddr;
+
+#[derive(Debug)]
+pub enum StartupError {
+    TcpBind {
+        listener: &'static str,
+        address: SocketAddr,
+        error: std::io::Error,
+    },
+    UnixSocketBind {
+        listener: &'static str,
+        path: String,
+        error: std::io::Error,
+    },
+    GrpcReflection(String),
+    Tls {
+        listener: &'static str,
+        error: String,
+    },
+}
+
+impl StartupError {
+    pub fn get_listener(&self) -> &'static str {
+        match self {
+            Self::TcpBind { listener, .. } => listener,
+            Self::UnixSocketBind { listener, .. } => listener,
+            Self::GrpcReflection(_) => "grpc",
+            Self::Tls { listener, .. } => listener,
+        }
+    }
+}
+
+impl std::fmt::Display for StartupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TcpBind {
+                listener,
+                address,
+                error,
+            } => write!(
+                f,
+                "Can not bind {} listener to {}. Err: {}",
+                listener, address, error
+            ),
+            Self::UnixSocketBind {
+                listener,
+                path,
+                error,
+            } => write!(
+                f,
+                "Can not bind {} listener to unix socket {}. Err: {}",
+                listener, path, error
+            ),
+            Self::GrpcReflection(err) => {
+                write!(f, "Can not build grpc reflection service. Err: {}", err)
+            }
+            Self::Tls { listener, error } => {
+                write!(
+                    f,
+                    "Can not load TLS config of {} listener. Err: {}",
+                    listener, error
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for StartupError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::TcpBind { error, .. } => Some(error),
+            Self::UnixSocketBind { error, .. } => Some(error),
+            Self::GrpcReflection(_) => None,
+            Self::Tls { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use arc_swap::ArcSwap;
+use async_trait::async_trait;
+use rust_extensions::MyTimerTick;
+use serde::Serialize;
+
+#[async_trait]
+pub trait HealthCheck {
+    async fn check(&self) -> Result<(), String>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HealthCheckOptions {
+    pub timeout: Duration,
+    pub cache_interval: Duration,
+    pub critical: bool,
+}
+
+impl Default for HealthCheckOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            cache_interval: Duration::from_secs(10),
+            critical: true,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct HealthCheckResult {
+    pub name: String,
+    pub healthy: bool,
+    pub critical: bool,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub checks: Vec<HealthCheckResult>,
+}
+
+struct RegisteredHealthCheck {
+    name: String,
+    options: HealthCheckOptions,
+    check: Arc<dyn HealthCheck + Send + Sync + 'static>,
+    last_result: Mutex<Option<(Instant, HealthCheckResult)>>,
+    // Held while the check runs, so concurrent cache misses wait for one run instead of starting their own.
+    refresh_lock: tokio::sync::Mutex<()>,
+}
+
+impl RegisteredHealthCheck {
+    fn get_cached_result(&self) -> Option<HealthCheckResult> {
+        self.last_result
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|(checked_at, _)| checked_at.elapsed() < self.options.cache_interval)
+            .map(|(_, result)| result.clone())
+    }
+
+    async fn get_result(&self) -> HealthCheckResult {
+        if let Some(result) = self.get_cached_result() {
+            return result;
+        }
+
+        let _refresh_lock = self.refresh_lock.lock().await;
+
+        // The check could be refreshed while we were waiting for the lock.
+        if let Some(result) = self.get_cached_result() {
+            return result;
+        }
+
+        let started = Instant::now();
+        let error = match tokio::time::timeout(self.options.timeout, self.check.check()).await {
+            Ok(Ok(())) => None,
+            Ok(Err(err)) => Some(err),
+            Err(_) => Some(format!("Timeout {:?}", self.options.timeout)),
+        };
+
+        let result = HealthCheckResult {
+            name: self.name.clone(),
+            healthy: error.is_none(),
+            critical: self.options.critical,
+            duration_ms: started.elapsed().as_millis() as u64,
+            error,
+        };
+
+        let status = if result.healthy { 1.0 } else { 0.0 };
+        let labels = &[("check", self.name.clone())];
+        metrics::gauge!("service_health_check_status", labels).set(status);
+
+        *self.last_result.lock().unwrap() = Some((Instant::now(), result.clone()));
+
+        result
+    }
+}
+
+pub struct HealthChecks {
+    checks: ArcSwap<Vec<Arc<RegisteredHealthCheck>>>,
+}
+
+impl Default for HealthChecks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthChecks {
+    pub fn new() -> Self {
+        Self {
+            checks: ArcSwap::from_pointee(Vec::new()),
+        }
+    }
+
+    /// A check with the name of an already registered one is ignored.
+    pub fn register(
+        &self,
+        name: impl Into<String>,
+        options: HealthCheckOptions,
+        check: Arc<dyn HealthCheck + Send + Sync + 'static>,
+    ) {
+        let registered = Arc::new(RegisteredHealthCheck {
+            name: name.into(),
+            options,
+            check,
+            last_result: Mutex::new(None),
+            refresh_lock: tokio::sync::Mutex::new(()),
+        });
+
+        self.checks.rcu(|prev| {
+            if prev.iter().any(|itm| itm.name == registered.name) {
+                return prev.clone();
+            }
+
+            let mut new = (**prev).clone();
+            new.push(registered.clone());
+            Arc::new(new)
+        });
+    }
+
+    pub async fn get_report(&self) -> HealthReport {
+        let checks = self.checks.load_full();
+
+        let running: Vec<_> = checks
+            .iter()
+            .map(|check| {
+                let check = check.clone();
+                tokio::spawn(async move { check.get_result().await })
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(running.len());
+        for (check, join_handle) in checks.iter().zip(running) {
+            let result = match join_handle.await {
+                Ok(result) => result,
+                Err(err) => HealthCheckResult {
+                    name: check.name.clone(),
+                    healthy: false,
+                    critical: check.options.critical,
+                    duration_ms: 0,
+                    error: Some(format!("Health check panicked: {}", err)),
+                },
+            };
+            results.push(result);
+        }
+
+        let status = if results.iter().any(|r| !r.healthy && r.critical) {
+            HealthStatus::Unhealthy
+        } else if results.iter().any(|r| !r.healthy) {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Healthy
+        };
+
+        HealthReport {
+            status,
+            checks: results,
+        }
+    }
+}
+
+pub(crate) struct HealthChecksTimerTick {
+    pub health_checks: Arc<HealthChecks>,
+}
+
+#[async_trait]
+impl MyTimerTick for HealthChecksTimerTick {
+    async fn tick(&self) {
+        self.health_checks.get_report().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    struct TestHealthCheck {
+        result: Result<(), String>,
+        delay: Duration,
+        runs: AtomicUsize,
+    }
+
+    impl TestHealthCheck {
+        fn new(result: Result<(), String>) -> Arc<Self> {
+            Self::with_delay(result, Duration::ZERO)
+        }
+
+        fn with_delay(result: Result<(), String>, delay: Duration) -> Arc<Self> {
+            Arc::new(Self {
+                result,
+                delay,
+                runs: AtomicUsize::new(0),
+            })
+        }
+
+        fn get_runs(&self) -> usize {
+            self.runs.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl HealthCheck for TestHealthCheck {
+        async fn check(&self) -> Result<(), String> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.result.clone()
+        }
+    }
+
+    fn get_options(critical: bool) -> HealthCheckOptions {
+        HealthCheckOptions {
+            critical,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_above_timeout_fails() {
+        let health_checks = HealthChecks::new();
+        health_checks.register(
+            "slow",
+            HealthCheckOptions {
+                timeout: Duration::from_millis(50),
+                ..Default::default()
+            },
+            TestHealthCheck::with_delay(Ok(()), Duration::from_secs(5)),
+        );
+
+        let started = Instant::now();
+        let report = health_checks.get_report().await;
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(report.status, HealthStatus::Unhealthy);
+        assert!(!report.checks[0].healthy);
+        assert_eq!(report.checks[0].error.as_deref(), Some("Timeout 50ms"));
+    }
+
+    #[tokio::test]
+    async fn test_result_is_cached() {
+        let health_checks = HealthChecks::new();
+        let cached = TestHealthCheck::new(Ok(()));
+        let not_cached = TestHealthCheck::new(Ok(()));
+
+        health_checks.register("cached", get_options(true), cached.clone());
+        health_checks.register(
+            "not-cached",
+            HealthCheckOptions {
+                cache_interval: Duration::ZERO,
+                ..Default::default()
+            },
+            not_cached.clone(),
+        );
+
+        health_checks.get_report().await;
+        health_checks.get_report().await;
+
+        assert_eq!(cached.get_runs(), 1);
+        assert_eq!(not_cached.get_runs(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_cache_misses_run_check_once() {
+        let health_checks = HealthChecks::new();
+        let check = TestHealthCheck::with_delay(Ok(()), Duration::from_millis(100));
+        health_checks.register("db", get_options(true), check.clone());
+
+        let (first, second) = tokio::join!(health_checks.get_report(), health_checks.get_report());
+
+        assert_eq!(check.get_runs(), 1);
+        assert_eq!(first.status, HealthStatus::Healthy);
+        assert_eq!(second.status, HealthStatus::Healthy);
+    }
+
+    #[tokio::test]
+    async fn test_checks_with_same_name_are_registered_once() {
+        let health_checks = HealthChecks::new();
+        let first = TestHealthCheck::new(Ok(()));
+        let second = TestHealthCheck::new(Err("Failed".to_string()));
+
+        health_checks.register("db", get_options(true), first.clone());
+        health_checks.register("db", get_options(true), second.clone());
+
+        let report = health_checks.get_report().await;
+
+        assert_eq!(report.checks.len(), 1);
+        assert_eq!(report.status, HealthStatus::Healthy);
+        assert_eq!(second.get_runs(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_status_aggregation() {
+        let health_checks = HealthChecks::new();
+        health_checks.register("db", get_options(true), TestHealthCheck::new(Ok(())));
+        assert_eq!(
+            health_checks.get_report().await.status,
+            HealthStatus::Healthy
+        );
+
+        health_checks.register(
+            "disk-space",
+            get_options(false),
+            TestHealthCheck::new(Err("Only 2% left".to_string())),
+        );
+        assert_eq!(
+            health_checks.get_report().await.status,
+            HealthStatus::Degraded
+        );
+
+        health_checks.register(
+            "downstream",
+            get_options(true),
+            TestHealthCheck::new(Err("Connection refused".to_string())),
+        );
+        let report = health_checks.get_report().await;
+
+        assert_eq!(report.status, HealthStatus::Unhealthy);
+        let failed: Vec<(&str, bool)> = report
+            .checks
+            .iter()
+            .filter(|itm| !itm.healthy)
+            .map(|itm| (itm.name.as_str(), itm.critical))
+            .collect();
+        assert_eq!(failed, vec![("disk-space", false), ("downstream", true)]);
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use my_http_server::*;
+
+use crate::{HealthChecks, HealthStatus};
+
+pub const HEALTH_PATH: &str = "/api/health";
+
+pub struct HealthMiddleware {
+    health_checks: Arc<HealthChecks>,
+}
+
+impl HealthMiddleware {
+    pub fn new(health_checks: Arc<HealthChecks>) -> Self {
+        Self { health_checks }
+    }
+}
+
+#[async_trait]
+impl HttpServerMiddleware for HealthMiddleware {
+    async fn handle_request(
+        &self,
+        ctx: &mut HttpContext,
+    ) -> Option<Result<HttpOkResult, HttpFailResult>> {
+        if ctx.request.http_path.as_str() != HEALTH_PATH {
+            return None;
+        }
+
+        let report = self.health_checks.get_report().await;
+
+        if report.status != HealthStatus::Unhealthy {
+            let response = HttpOutput::from_builder()
+                .set_content_as_json(report)
+                .into_ok_result(false);
+            return Some(response);
+        }
+
+        let response = HttpOutput::from_builder()
+            .set_status_code(503)
+            .set_content_as_json(report)
+            .into_err(false, false);
+
+        Some(response)
+    }
+}
@@ -0,0 +1,20 @@
+mod health_checks;
+pub use health_checks::*;
+mod health_middleware;
+pub use health_middleware::*;
+mod readiness;
+pub use readiness::*;
+mod readiness_middleware;
+pub use readiness_middleware::*;
+#[cfg(feature = "my-nosql-data-reader-sdk")]
+mod no_sql_reader_readiness;
+#[cfg(feature = "my-nosql-data-reader-sdk")]
+pub use no_sql_reader_readiness::*;
+#[cfg(feature = "my-service-bus")]
+mod sb_client_readiness;
+#[cfg(feature = "my-service-bus")]
+pub use sb_client_readiness::*;
+#[cfg(feature = "postgres")]
+mod postgres_readiness;
+#[cfg(feature = "postgres")]
+pub use postgres_readiness::*;
@@ -0,0 +1,59 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use my_no_sql_sdk::abstractions::{MyNoSqlEntity, MyNoSqlEntitySerializer};
+use my_no_sql_sdk::reader::MyNoSqlDataReaderTcp;
+
+use crate::ReadinessCheck;
+
+const FIRST_DATA_WAIT_TIMEOUT: Duration = Duration::from_millis(100);
+
+pub(crate) struct NoSqlReaderReadinessCheck<
+    TMyNoSqlEntity: MyNoSqlEntity + MyNoSqlEntitySerializer + Sync + Send + 'static,
+> {
+    name: String,
+    reader: Arc<MyNoSqlDataReaderTcp<TMyNoSqlEntity>>,
+    synced: AtomicBool,
+}
+
+impl<TMyNoSqlEntity: MyNoSqlEntity + MyNoSqlEntitySerializer + Sync + Send + 'static>
+    NoSqlReaderReadinessCheck<TMyNoSqlEntity>
+{
+    pub fn new(reader: Arc<MyNoSqlDataReaderTcp<TMyNoSqlEntity>>) -> Self {
+        Self {
+            name: format!("nosql-reader:{}", TMyNoSqlEntity::TABLE_NAME),
+            reader,
+            synced: AtomicBool::new(false),
+        }
+    }
+}
+
+#[async_trait]
+impl<TMyNoSqlEntity: MyNoSqlEntity + MyNoSqlEntitySerializer + Sync + Send + 'static> ReadinessCheck
+    for NoSqlReaderReadinessCheck<TMyNoSqlEntity>
+{
+    fn get_name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    async fn is_ready(&self) -> Result<(), String> {
+        if self.synced.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let first_data = tokio::time::timeout(
+            FIRST_DATA_WAIT_TIMEOUT,
+            self.reader.wait_until_first_data_arrives(),
+        )
+        .await;
+
+        if first_data.is_err() {
+            return Err("Table is not synced yet".to_string());
+        }
+
+        self.synced.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
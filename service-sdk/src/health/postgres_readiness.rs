@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use my_postgres::MyPostgres;
+
+use crate::ReadinessCheck;
+
+pub(crate) struct PostgresReadinessCheck {
+    name: String,
+    postgres: Arc<MyPostgres>,
+}
+
+impl PostgresReadinessCheck {
+    pub fn new(name: &str, postgres: Arc<MyPostgres>) -> Self {
+        Self {
+            name: format!("postgres:{}", name),
+            postgres,
+        }
+    }
+}
+
+#[async_trait]
+impl ReadinessCheck for PostgresReadinessCheck {
+    fn get_name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    async fn is_ready(&self) -> Result<(), String> {
+        if !self.postgres.is_connected() {
+            return Err("Not connected to Postgres".to_string());
+        }
+
+        Ok(())
+    }
+}
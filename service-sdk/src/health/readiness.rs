@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use async_trait::async_trait;
+use rust_extensions::AppStates;
+use serde::Serialize;
+
+use crate::GracefulShutdown;
+
+#[async_trait]
+pub trait ReadinessCheck {
+    fn get_name(&self) -> &str;
+    async fn is_ready(&self) -> Result<(), String>;
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReadinessCheckResult {
+    pub name: String,
+    pub ready: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub checks: Vec<ReadinessCheckResult>,
+}
+
+pub struct ReadinessChecks {
+    checks: ArcSwap<Vec<Arc<dyn ReadinessCheck + Send + Sync + 'static>>>,
+}
+
+impl Default for ReadinessChecks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReadinessChecks {
+    pub fn new() -> Self {
+        Self {
+            checks: ArcSwap::from_pointee(Vec::new()),
+        }
+    }
+
+    /// A check with the name of an already registered one is ignored, so a table or a connection is checked once.
+    pub fn register(&self, check: Arc<dyn ReadinessCheck + Send + Sync + 'static>) {
+        self.checks.rcu(|prev| {
+            if prev.iter().any(|itm| itm.get_name() == check.get_name()) {
+                return prev.clone();
+            }
+
+            let mut new = (**prev).clone();
+            new.push(check.clone());
+            Arc::new(new)
+        });
+    }
+
+    pub async fn get_report(&self) -> ReadinessReport {
+        let checks = self.checks.load_full();
+
+        let result: Vec<ReadinessCheckResult> =
+            futures::future::join_all(checks.iter().map(|check| async move {
+                let error = check.is_ready().await.err();
+                ReadinessCheckResult {
+                    name: check.get_name().to_string(),
+                    ready: error.is_none(),
+                    error,
+                }
+            }))
+            .await;
+
+        ReadinessReport {
+            ready: result.iter().all(|check| check.ready),
+            checks: result,
+        }
+    }
+}
+
+pub(crate) struct AppStateReadinessCheck {
+    pub app_states: Arc<AppStates>,
+    pub graceful_shutdown: Arc<GracefulShutdown>,
+}
+
+#[async_trait]
+impl ReadinessCheck for AppStateReadinessCheck {
+    fn get_name(&self) -> &str {
+        "application"
+    }
+
+    async fn is_ready(&self) -> Result<(), String> {
+        if !self.app_states.is_initialized() {
+            return Err("Application is not initialized yet".to_string());
+        }
+
+        if !self.graceful_shutdown.is_ready() {
+            return Err("Application is shutting down".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    struct TestReadinessCheck {
+        name: &'static str,
+        result: Result<(), String>,
+        barrier: Option<Arc<tokio::sync::Barrier>>,
+    }
+
+    impl TestReadinessCheck {
+        fn new(name: &'static str, result: Result<(), String>) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                result,
+                barrier: None,
+            })
+        }
+    }
+
+    #[async_trait]
+    impl ReadinessCheck for TestReadinessCheck {
+        fn get_name(&self) -> &str {
+            self.name
+        }
+
+        async fn is_ready(&self) -> Result<(), String> {
+            if let Some(barrier) = self.barrier.as_ref() {
+                barrier.wait().await;
+            }
+            self.result.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_checks_with_same_name_are_registered_once() {
+        let checks = ReadinessChecks::new();
+        checks.register(TestReadinessCheck::new("db", Ok(())));
+        checks.register(TestReadinessCheck::new("db", Err("Not ready".to_string())));
+        checks.register(TestReadinessCheck::new("cache", Ok(())));
+
+        let report = checks.get_report().await;
+
+        assert!(report.ready);
+        let names: Vec<&str> = report.checks.iter().map(|itm| itm.name.as_str()).collect();
+        assert_eq!(names, vec!["db", "cache"]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_check_makes_service_not_ready() {
+        let checks = ReadinessChecks::new();
+        checks.register(TestReadinessCheck::new(
+            "db",
+            Err("Connection refused".to_string()),
+        ));
+        checks.register(TestReadinessCheck::new("cache", Ok(())));
+
+        let report = checks.get_report().await;
+
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"ready":false,"checks":[{"name":"db","ready":false,"error":"Connection refused"},{"name":"cache","ready":true}]}"#
+        );
+    }
+
+    // Each check waits for the other one: run one by one they would never finish.
+    #[tokio::test]
+    async fn test_checks_run_concurrently() {
+        let barrier = Arc::new(tokio::sync::Barrier::new(2));
+        let checks = ReadinessChecks::new();
+
+        for name in ["db", "cache"] {
+            checks.register(Arc::new(TestReadinessCheck {
+                name,
+                result: Ok(()),
+                barrier: Some(barrier.clone()),
+            }));
+        }
+
+        let report = tokio::time::timeout(Duration::from_secs(5), checks.get_report())
+            .await
+            .unwrap();
+
+        assert!(report.ready);
+    }
+}
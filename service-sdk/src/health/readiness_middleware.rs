@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use my_http_server::*;
+
+use crate::ReadinessChecks;
+
+pub const READINESS_PATH: &str = "/api/ready";
+
+pub struct ReadinessMiddleware {
+    checks: Arc<ReadinessChecks>,
+}
+
+impl ReadinessMiddleware {
+    pub fn new(checks: Arc<ReadinessChecks>) -> Self {
+        Self { checks }
+    }
+}
+
+#[async_trait]
+impl HttpServerMiddleware for ReadinessMiddleware {
+    async fn handle_request(
+        &self,
+        ctx: &mut HttpContext,
+    ) -> Option<Result<HttpOkResult, HttpFailResult>> {
+        if ctx.request.http_path.as_str() != READINESS_PATH {
+            return None;
+        }
+
+        let report = self.checks.get_report().await;
+
+        if report.ready {
+            let response = HttpOutput::from_builder()
+                .set_content_as_json(report)
+                .into_ok_result(false);
+            return Some(response);
+        }
+
+        let response = HttpOutput::from_builder()
+            .set_status_code(503)
+            .set_content_as_json(report)
+            .into_err(false, false);
+
+        Some(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    use rust_extensions::AppStates;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    use super::*;
+    use crate::{HttpServerBuilder, ReadinessCheck};
+
+    struct FailedReadinessCheck;
+
+    #[async_trait]
+    impl ReadinessCheck for FailedReadinessCheck {
+        fn get_name(&self) -> &str {
+            "db"
+        }
+
+        async fn is_ready(&self) -> Result<(), String> {
+            Err("Connection refused".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_not_ready_service_answers_503_with_report() {
+        let readiness_checks = Arc::new(ReadinessChecks::new());
+        readiness_checks.register(Arc::new(FailedReadinessCheck));
+
+        // The port is taken from a listener which is dropped right away: the server binds it again.
+        let addr = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let mut builder = HttpServerBuilder::new("sdk-test", "1.0.0");
+        builder.update_listen_endpoint(addr.ip(), addr.port());
+        builder.set_readiness_checks(readiness_checks);
+        builder.bind().unwrap();
+
+        let app_states = Arc::new(AppStates::create_initialized());
+        let _http_servers = builder.start(&app_states);
+        builder
+            .wait_until_listening(Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        let mut tcp_stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            READINESS_PATH
+        );
+        tcp_stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        tcp_stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        assert_eq!(
+            body,
+            r#"{"ready":false,"checks":[{"name":"db","ready":false,"error":"Connection refused"}]}"#
+        );
+    }
+}
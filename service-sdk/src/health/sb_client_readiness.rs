@@ -0,0 +1,25 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use my_service_bus::client::MyServiceBusClient;
+
+use crate::ReadinessCheck;
+
+pub(crate) struct SbClientReadinessCheck {
+    pub sb_client: Arc<MyServiceBusClient>,
+}
+
+#[async_trait]
+impl ReadinessCheck for SbClientReadinessCheck {
+    fn get_name(&self) -> &str {
+        "service-bus"
+    }
+
+    async fn is_ready(&self) -> Result<(), String> {
+        if !self.sb_client.is_connected() {
+            return Err("Not connected to Service Bus".to_string());
+        }
+
+        Ok(())
+    }
+}
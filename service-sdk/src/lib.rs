@@ -1,12 +1,22 @@
 mod builders;
 mod common;
+mod health;
+mod running_service;
 mod sdk_metrics;
 mod service_context;
+mod shutdown;
+#[cfg(feature = "with-tls")]
+mod tls;
 
 pub use builders::*;
 pub use common::*;
+pub use health::*;
+pub use running_service::*;
 pub use sdk_metrics::*;
 pub use service_context::*;
+pub use shutdown::*;
+#[cfg(feature = "with-tls")]
+pub use tls::*;
 
 pub extern crate my_http_server;
 pub extern crate my_telemetry;
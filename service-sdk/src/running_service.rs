@@ -0,0 +1,20 @@
+use crate::ServiceContext;
+
+pub struct RunningService<'s> {
+    service_context: &'s mut ServiceContext,
+}
+
+impl<'s> RunningService<'s> {
+    pub(crate) fn new(service_context: &'s mut ServiceContext) -> Self {
+        Self { service_context }
+    }
+
+    pub fn get_service_context(&self) -> &ServiceContext {
+        self.service_context
+    }
+
+    pub async fn wait_until_shutdown(self) {
+        crate::wait_for_shutdown_signal(&self.service_context.app_states).await;
+        self.service_context.shutdown().await;
+    }
+}
@@ -1,27 +1,161 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use arc_swap::ArcSwap;
 use async_trait::async_trait;
 use rust_extensions::MyTimerTick;
 
 type LabelsKey = Vec<(String, String)>;
-type CountersMap = HashMap<LabelsKey, Arc<AtomicU64>>;
+type CountersMap = HashMap<LabelsKey, Arc<LabelsCounter>>;
+
+pub const DEFAULT_LABEL_SET_TTL: Duration = Duration::from_secs(300);
+
+/// Label value of the label set which collects events above `max_label_sets`.
+pub const OVERFLOW_LABEL_VALUE: &str = "overflow";
+
+// Value of a counter removed by the timer. Increments which find it go to a fresh counter instead.
+const EVICTED: u64 = u64::MAX;
+
+/// What the gauge of `EventsPerSecondCounter` shows. Windows are measured in one-second buckets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventsWindow {
+    /// Events of the last second.
+    PerSecond,
+    /// Events of the last 60 seconds.
+    PerMinute,
+    /// Events per second averaged over the last N seconds.
+    RollingAverage(usize),
+    /// The busiest second within the last N seconds.
+    Peak(usize),
+}
+
+impl EventsWindow {
+    fn get_size(&self) -> usize {
+        match self {
+            Self::PerSecond => 1,
+            Self::PerMinute => 60,
+            Self::RollingAverage(size) => *size,
+            Self::Peak(size) => *size,
+        }
+    }
+
+    fn get_value(&self, history: &VecDeque<u64>) -> f64 {
+        match self {
+            Self::PerSecond | Self::PerMinute => history.iter().sum::<u64>() as f64,
+            Self::RollingAverage(_) => {
+                if history.is_empty() {
+                    return 0.0;
+                }
+                history.iter().sum::<u64>() as f64 / history.len() as f64
+            }
+            Self::Peak(_) => history.iter().max().copied().unwrap_or(0) as f64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EventsCounterOptions {
+    pub window: EventsWindow,
+    /// A label set without events for this long is removed together with its series. The one without labels is kept.
+    /// Rounded up to whole seconds, the resolution of the counter.
+    pub label_set_ttl: Option<Duration>,
+    /// Events of new label sets above the limit go to the label set with every value set to `overflow`.
+    pub max_label_sets: Option<usize>,
+}
+
+impl Default for EventsCounterOptions {
+    fn default() -> Self {
+        Self {
+            window: EventsWindow::PerSecond,
+            label_set_ttl: Some(DEFAULT_LABEL_SET_TTL),
+            max_label_sets: None,
+        }
+    }
+}
+
+struct LabelsState {
+    history: VecDeque<u64>,
+    idle_ticks: u64,
+}
+
+struct LabelsCounter {
+    current: AtomicU64,
+    // Accessed only by the timer.
+    state: Mutex<LabelsState>,
+}
+
+impl LabelsCounter {
+    fn new() -> Self {
+        Self {
+            current: AtomicU64::new(0),
+            state: Mutex::new(LabelsState {
+                history: VecDeque::new(),
+                idle_ticks: 0,
+            }),
+        }
+    }
+
+    fn add(&self, n: u64) -> bool {
+        self.current
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |value| {
+                if value == EVICTED {
+                    None
+                } else {
+                    Some(value.saturating_add(n).min(EVICTED - 1))
+                }
+            })
+            .is_ok()
+    }
+
+    fn is_evicted(&self) -> bool {
+        self.current.load(Ordering::Relaxed) == EVICTED
+    }
+
+    // Succeeds only if no events came since the last tick.
+    fn try_evict(&self) -> bool {
+        self.current
+            .compare_exchange(0, EVICTED, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    }
+}
 
 pub struct EventsPerSecondCounter {
     metric_name: String,
+    options: EventsCounterOptions,
+    window_size: usize,
+    label_set_ttl_ticks: Option<u64>,
     counters: ArcSwap<CountersMap>,
+    gauge: Mutex<Option<Result<EventsGauge, String>>>,
 }
 
 impl EventsPerSecondCounter {
-    pub(crate) fn new(metric_name: impl Into<String>) -> Self {
+    pub(crate) fn new(metric_name: impl Into<String>, options: EventsCounterOptions) -> Self {
+        let metric_name = metric_name.into();
+        let window_size = options.window.get_size();
+
+        if window_size == 0 {
+            panic!(
+                "Events counter {} can not have an empty window {:?}. Window size must be at least 1 second",
+                metric_name, options.window
+            );
+        }
+
         Self {
-            metric_name: metric_name.into(),
+            metric_name,
+            options,
+            window_size,
+            label_set_ttl_ticks: options.label_set_ttl.map(get_ttl_ticks),
             counters: ArcSwap::from_pointee(HashMap::new()),
+            gauge: Mutex::new(None),
         }
     }
 
+    pub fn get_window(&self) -> EventsWindow {
+        self.options.window
+    }
+
     pub fn increment(&self) {
         self.increment_by_with_labels(1, &[]);
     }
@@ -37,47 +171,251 @@ impl EventsPerSecondCounter {
     pub fn increment_by_with_labels(&self, n: u64, labels: &[(&str, &str)]) {
         let key = normalize_labels(labels);
 
-        if let Some(atomic) = self.counters.load().get(&key) {
-            atomic.fetch_add(n, Ordering::Relaxed);
+        if self.try_add(&key, n, true) {
             return;
         }
 
+        metrics::counter!(
+            "events_counter_dropped_events",
+            &[("metric", self.metric_name.clone())]
+        )
+        .increment(n);
+
+        self.try_add(&get_overflow_key(&key), n, false);
+    }
+
+    /// Fails only if the label set does not exist and the limit of label sets is reached.
+    fn try_add(&self, key: &LabelsKey, n: u64, check_limit: bool) -> bool {
+        // A label set evicted by the timer in the meantime is created again.
+        loop {
+            let counter = match self.counters.load().get(key) {
+                Some(counter) if !counter.is_evicted() => counter.clone(),
+                _ => match self.try_insert(key, check_limit) {
+                    Some(counter) => counter,
+                    None if check_limit && self.is_full(&self.counters.load()) => return false,
+                    None => continue,
+                },
+            };
+
+            if counter.add(n) {
+                return true;
+            }
+        }
+    }
+
+    /// Returns `None` if the limit of label sets is reached or if the timer evicted the new counter right away.
+    fn try_insert(&self, key: &LabelsKey, check_limit: bool) -> Option<Arc<LabelsCounter>> {
         self.counters.rcu(|prev| {
-            if prev.contains_key(&key) {
+            let exists = prev.get(key).is_some_and(|counter| !counter.is_evicted());
+            if exists || (check_limit && self.is_full(prev)) {
                 return prev.clone();
             }
             let mut new = (**prev).clone();
-            new.insert(key.clone(), Arc::new(AtomicU64::new(0)));
+            new.insert(key.clone(), Arc::new(LabelsCounter::new()));
             Arc::new(new)
         });
 
         self.counters
             .load()
-            .get(&key)
-            .expect("entry just inserted via rcu")
-            .fetch_add(n, Ordering::Relaxed);
+            .get(key)
+            .filter(|counter| !counter.is_evicted())
+            .cloned()
+    }
+
+    // Evicted counters wait only for the timer to remove them: they do not take a place.
+    fn is_full(&self, counters: &CountersMap) -> bool {
+        match self.options.max_label_sets {
+            Some(max_label_sets) => {
+                counters
+                    .iter()
+                    .filter(|(key, counter)| !is_overflow_key(key) && !counter.is_evicted())
+                    .count()
+                    >= max_label_sets
+            }
+            None => false,
+        }
     }
 
     fn tick(&self) {
-        let snapshot: Vec<(LabelsKey, u64)> = self
+        let mut evicted = vec![];
+
+        let snapshot: Vec<(LabelsKey, f64)> = self
             .counters
             .load()
             .iter()
-            .map(|(labels, atomic)| (labels.clone(), atomic.swap(0, Ordering::Relaxed)))
+            .filter_map(|(labels, counter)| {
+                let value = counter.current.swap(0, Ordering::Relaxed);
+
+                let mut state = counter.state.lock().unwrap();
+                state.history.push_back(value);
+                while state.history.len() > self.window_size {
+                    state.history.pop_front();
+                }
+
+                if value > 0 {
+                    state.idle_ticks = 0;
+                } else {
+                    state.idle_ticks += 1;
+                }
+
+                if let Some(label_set_ttl_ticks) = self.label_set_ttl_ticks {
+                    if !labels.is_empty()
+                        && state.idle_ticks >= label_set_ttl_ticks
+                        && counter.try_evict()
+                    {
+                        evicted.push((labels.clone(), counter.clone()));
+                        return None;
+                    }
+                }
+
+                Some((
+                    labels.clone(),
+                    self.options.window.get_value(&state.history),
+                ))
+            })
             .collect();
 
-        for (labels, value) in snapshot {
-            let name = self.metric_name.clone();
-            if labels.is_empty() {
-                metrics::gauge!(name).set(value as f64);
-            } else {
-                metrics::gauge!(name, &labels).set(value as f64);
+        if !evicted.is_empty() {
+            self.remove_evicted(&evicted);
+        }
+
+        if let Some(gauge) = self.get_gauge() {
+            gauge.set_values(snapshot);
+        }
+    }
+
+    fn remove_evicted(&self, evicted: &[(LabelsKey, Arc<LabelsCounter>)]) {
+        self.counters.rcu(|prev| {
+            let mut new = (**prev).clone();
+            for (labels, counter) in evicted.iter() {
+                // An increment could already replace the evicted counter with a fresh one.
+                if new.get(labels).is_some_and(|itm| Arc::ptr_eq(itm, counter)) {
+                    new.remove(labels);
+                }
             }
+            Arc::new(new)
+        });
+    }
+
+    fn get_gauge(&self) -> Option<EventsGauge> {
+        let mut gauge = self.gauge.lock().unwrap();
+
+        if gauge.is_none() {
+            let result = EventsGauge::register(self.metric_name.as_str());
+
+            if let Err(err) = &result {
+                my_logger::LOGGER.write_error(
+                    "EventsPerSecondCounter".to_string(),
+                    format!("Can not register gauge {}. Err: {}", self.metric_name, err),
+                    my_logger::LogEventCtx::new(),
+                );
+            }
+
+            *gauge = Some(result);
+        }
+
+        gauge.as_ref()?.as_ref().ok().cloned()
+    }
+}
+
+/// Label sets of a counter may have different label names, so the gauge is exported as a family
+/// of its own instead of a `GaugeVec` with label names fixed by the first label set.
+/// Series of evicted label sets are gone with the next snapshot.
+#[derive(Clone)]
+struct EventsGauge {
+    desc: prometheus::core::Desc,
+    const_labels: BTreeMap<String, String>,
+    values: Arc<Mutex<Vec<(LabelsKey, f64)>>>,
+}
+
+impl EventsGauge {
+    fn register(metric_name: &str) -> Result<Self, String> {
+        let desc = super::get_prometheus_opts(metric_name, &[])
+            .describe()
+            .map_err(|err| err.to_string())?;
+
+        let result = Self {
+            desc,
+            const_labels: super::get_metrics_config().load().constant_labels.clone(),
+            values: Default::default(),
+        };
+
+        prometheus::default_registry()
+            .register(Box::new(result.clone()))
+            .map_err(|err| err.to_string())?;
+
+        Ok(result)
+    }
+
+    fn set_values(&self, values: Vec<(LabelsKey, f64)>) {
+        *self.values.lock().unwrap() = values;
+    }
+}
+
+impl prometheus::core::Collector for EventsGauge {
+    fn desc(&self) -> Vec<&prometheus::core::Desc> {
+        vec![&self.desc]
+    }
+
+    fn collect(&self) -> Vec<prometheus::proto::MetricFamily> {
+        let values = self.values.lock().unwrap();
+        if values.is_empty() {
+            return vec![];
         }
+
+        let metrics: Vec<prometheus::proto::Metric> = values
+            .iter()
+            .map(|(labels, value)| {
+                // Labels of the set win over constant labels with the same name.
+                let mut all_labels = self.const_labels.clone();
+                all_labels.extend(labels.iter().cloned());
+
+                let label_pairs: Vec<prometheus::proto::LabelPair> = all_labels
+                    .into_iter()
+                    .map(|(name, value)| {
+                        let mut label_pair = prometheus::proto::LabelPair::default();
+                        label_pair.set_name(name);
+                        label_pair.set_value(value);
+                        label_pair
+                    })
+                    .collect();
+
+                let mut gauge = prometheus::proto::Gauge::default();
+                gauge.set_value(*value);
+
+                let mut metric = prometheus::proto::Metric::default();
+                metric.set_label(label_pairs.into());
+                metric.set_gauge(gauge);
+                metric
+            })
+            .collect();
+
+        let mut family = prometheus::proto::MetricFamily::default();
+        family.set_name(self.desc.fq_name.clone());
+        family.set_help(self.desc.help.clone());
+        family.set_field_type(prometheus::proto::MetricType::GAUGE);
+        family.set_metric(metrics.into());
+
+        vec![family]
     }
 }
 
-fn normalize_labels(labels: &[(&str, &str)]) -> LabelsKey {
+fn get_ttl_ticks(label_set_ttl: Duration) -> u64 {
+    let ticks = label_set_ttl.as_secs() + u64::from(label_set_ttl.subsec_nanos() > 0);
+    ticks.max(1)
+}
+
+fn get_overflow_key(key: &LabelsKey) -> LabelsKey {
+    key.iter()
+        .map(|(name, _)| (name.clone(), OVERFLOW_LABEL_VALUE.to_string()))
+        .collect()
+}
+
+fn is_overflow_key(key: &LabelsKey) -> bool {
+    !key.is_empty() && key.iter().all(|(_, value)| value == OVERFLOW_LABEL_VALUE)
+}
+
+pub(crate) fn normalize_labels(labels: &[(&str, &str)]) -> LabelsKey {
     let mut owned: LabelsKey = labels
         .iter()
         .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
@@ -99,3 +437,216 @@ impl MyTimerTick for EventsPerSecondTimerTick {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_counter(metric_name: &str, options: EventsCounterOptions) -> EventsPerSecondCounter {
+        EventsPerSecondCounter::new(metric_name, options)
+    }
+
+    fn get_value(counter: &EventsPerSecondCounter, labels: &[(&str, &str)]) -> Option<u64> {
+        let counters = counter.counters.load();
+        let labels_counter = counters.get(&normalize_labels(labels))?;
+        Some(labels_counter.current.load(Ordering::Relaxed))
+    }
+
+    fn get_window_value(window: EventsWindow, history: &[u64]) -> f64 {
+        window.get_value(&history.iter().copied().collect())
+    }
+
+    #[test]
+    fn test_window_values() {
+        let history = [3, 0, 7, 2];
+
+        assert_eq!(get_window_value(EventsWindow::PerSecond, &[5]), 5.0);
+        assert_eq!(get_window_value(EventsWindow::PerMinute, &history), 12.0);
+        assert_eq!(
+            get_window_value(EventsWindow::RollingAverage(4), &history),
+            3.0
+        );
+        assert_eq!(get_window_value(EventsWindow::Peak(4), &history), 7.0);
+    }
+
+    #[test]
+    fn test_empty_history_is_zero() {
+        assert_eq!(get_window_value(EventsWindow::PerSecond, &[]), 0.0);
+        assert_eq!(get_window_value(EventsWindow::PerMinute, &[]), 0.0);
+        assert_eq!(get_window_value(EventsWindow::RollingAverage(5), &[]), 0.0);
+        assert_eq!(get_window_value(EventsWindow::Peak(5), &[]), 0.0);
+    }
+
+    #[test]
+    fn test_window_sizes() {
+        assert_eq!(EventsWindow::PerSecond.get_size(), 1);
+        assert_eq!(EventsWindow::PerMinute.get_size(), 60);
+        assert_eq!(EventsWindow::RollingAverage(10).get_size(), 10);
+        assert_eq!(EventsWindow::Peak(5).get_size(), 5);
+    }
+
+    #[test]
+    fn test_history_is_trimmed_to_window() {
+        let counter = get_counter(
+            "test_events_counter_window",
+            EventsCounterOptions {
+                window: EventsWindow::RollingAverage(2),
+                ..Default::default()
+            },
+        );
+
+        for n in [4, 6, 10] {
+            counter.increment_by(n);
+            counter.tick();
+        }
+
+        let counters = counter.counters.load();
+        let state = counters
+            .get(&normalize_labels(&[]))
+            .unwrap()
+            .state
+            .lock()
+            .unwrap();
+        assert_eq!(state.history, VecDeque::from([6, 10]));
+        assert_eq!(counter.get_window().get_value(&state.history), 8.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "can not have an empty window")]
+    fn test_empty_rolling_average_is_rejected() {
+        get_counter(
+            "test_events_counter_empty_average",
+            EventsCounterOptions {
+                window: EventsWindow::RollingAverage(0),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "can not have an empty window")]
+    fn test_empty_peak_is_rejected() {
+        get_counter(
+            "test_events_counter_empty_peak",
+            EventsCounterOptions {
+                window: EventsWindow::Peak(0),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn test_label_set_with_pending_events_is_not_evicted() {
+        let counter = LabelsCounter::new();
+
+        assert!(counter.add(2));
+        assert!(!counter.try_evict());
+
+        counter.current.swap(0, Ordering::Relaxed);
+        assert!(counter.try_evict());
+        assert!(counter.is_evicted());
+        assert!(!counter.add(1));
+    }
+
+    #[test]
+    fn test_ttl_is_rounded_up_to_ticks() {
+        assert_eq!(get_ttl_ticks(Duration::from_millis(1)), 1);
+        assert_eq!(get_ttl_ticks(Duration::from_secs(1)), 1);
+        assert_eq!(get_ttl_ticks(Duration::from_millis(1500)), 2);
+        assert_eq!(get_ttl_ticks(Duration::ZERO), 1);
+    }
+
+    #[test]
+    fn test_idle_label_set_is_evicted_after_ttl() {
+        let counter = get_counter(
+            "test_events_counter_ttl",
+            EventsCounterOptions {
+                label_set_ttl: Some(Duration::from_secs(2)),
+                ..Default::default()
+            },
+        );
+
+        counter.increment();
+        counter.increment_with_labels(&[("venue", "a")]);
+
+        counter.tick();
+        counter.tick();
+        assert_eq!(get_value(&counter, &[("venue", "a")]), Some(0));
+
+        counter.tick();
+        assert_eq!(get_value(&counter, &[("venue", "a")]), None);
+        // The label set without labels is kept.
+        assert_eq!(get_value(&counter, &[]), Some(0));
+
+        counter.increment_with_labels(&[("venue", "a")]);
+        assert_eq!(get_value(&counter, &[("venue", "a")]), Some(1));
+    }
+
+    #[test]
+    fn test_increment_racing_eviction_goes_to_a_new_label_set() {
+        let counter = get_counter("test_events_counter_race", Default::default());
+        let key = normalize_labels(&[("venue", "a")]);
+
+        counter.increment_with_labels(&[("venue", "a")]);
+        let evicted_counter = counter.counters.load().get(&key).cloned().unwrap();
+        evicted_counter.current.swap(0, Ordering::Relaxed);
+
+        // The timer evicts the counter, the increment comes before it is removed from the map.
+        assert!(evicted_counter.try_evict());
+        counter.increment_with_labels(&[("venue", "a")]);
+        counter.remove_evicted(&[(key.clone(), evicted_counter)]);
+
+        assert_eq!(get_value(&counter, &[("venue", "a")]), Some(1));
+    }
+
+    #[test]
+    fn test_label_sets_above_limit_go_to_overflow() {
+        let counter = get_counter(
+            "test_events_counter_overflow",
+            EventsCounterOptions {
+                max_label_sets: Some(1),
+                ..Default::default()
+            },
+        );
+
+        counter.increment_with_labels(&[("venue", "a")]);
+        counter.increment_by_with_labels(2, &[("venue", "b")]);
+        counter.increment_by_with_labels(3, &[("venue", "c")]);
+
+        assert_eq!(get_value(&counter, &[("venue", "a")]), Some(1));
+        assert_eq!(get_value(&counter, &[("venue", "b")]), None);
+        assert_eq!(get_value(&counter, &[("venue", "c")]), None);
+        assert_eq!(
+            get_value(&counter, &[("venue", OVERFLOW_LABEL_VALUE)]),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn test_evicted_label_set_does_not_take_a_place() {
+        let counter = get_counter(
+            "test_events_counter_evicted_place",
+            EventsCounterOptions {
+                max_label_sets: Some(1),
+                ..Default::default()
+            },
+        );
+        let key = normalize_labels(&[("venue", "a")]);
+
+        counter.increment_with_labels(&[("venue", "a")]);
+        let evicted_counter = counter.counters.load().get(&key).cloned().unwrap();
+        evicted_counter.current.swap(0, Ordering::Relaxed);
+        assert!(evicted_counter.try_evict());
+
+        // The evicted counter is still in the map until the timer removes it.
+        counter.increment_with_labels(&[("venue", "b")]);
+        assert_eq!(get_value(&counter, &[("venue", "b")]), Some(1));
+
+        // The limit is reached by `b`: the evicted `a` goes to overflow instead of spinning on its counter.
+        counter.increment_with_labels(&[("venue", "a")]);
+        assert_eq!(
+            get_value(&counter, &[("venue", OVERFLOW_LABEL_VALUE)]),
+            Some(1)
+        );
+    }
+}
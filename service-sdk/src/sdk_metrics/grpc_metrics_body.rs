@@ -0,0 +1,440 @@
+use std::collections::HashSet;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use my_grpc_extensions::hyper::body::{Body as HttpBody, Bytes, Frame, SizeHint};
+use my_grpc_extensions::hyper::HeaderMap;
+use my_grpc_extensions::tonic::{body::Body, Status};
+
+use super::set_histogram_exemplar;
+
+const UNKNOWN_LABEL_VALUE: &str = "unknown";
+
+pub(crate) struct GrpcCallLabels {
+    pub grpc_service: String,
+    pub grpc_method: String,
+}
+
+impl GrpcCallLabels {
+    /// Paths of services which are not served end up in one `unknown` series, so scanners can not blow up the series count.
+    pub fn from_path(path: &str, known_services: &HashSet<String>) -> Self {
+        let path = path.trim_start_matches('/');
+
+        match path.split_once('/') {
+            Some((grpc_service, grpc_method))
+                if !grpc_method.is_empty() && known_services.contains(grpc_service) =>
+            {
+                Self {
+                    grpc_service: grpc_service.to_string(),
+                    grpc_method: grpc_method.to_string(),
+                }
+            }
+            _ => Self {
+                grpc_service: UNKNOWN_LABEL_VALUE.to_string(),
+                grpc_method: UNKNOWN_LABEL_VALUE.to_string(),
+            },
+        }
+    }
+
+    fn get_labels(&self) -> [(&'static str, String); 2] {
+        [
+            ("grpc_service", self.grpc_service.clone()),
+            ("grpc_method", self.grpc_method.clone()),
+        ]
+    }
+
+    fn get_labels_with_code(&self, grpc_code: &'static str) -> [(&'static str, String); 3] {
+        [
+            ("grpc_service", self.grpc_service.clone()),
+            ("grpc_method", self.grpc_method.clone()),
+            ("grpc_code", grpc_code.to_string()),
+        ]
+    }
+}
+
+/// Tracks a single call from the moment the request arrives until the response stream is over.
+pub(crate) struct GrpcStreamRecorder {
+    labels: GrpcCallLabels,
+    sw: stopwatch::Stopwatch,
+    time_to_headers: Option<Duration>,
+    messages_received: Arc<AtomicU64>,
+    messages_sent: u64,
+    trace_id: Option<String>,
+    finished: bool,
+}
+
+impl GrpcStreamRecorder {
+    pub fn start(labels: GrpcCallLabels, trace_id: Option<String>) -> Self {
+        metrics::gauge!("grpc_streams_in_flight", &labels.get_labels()).increment(1);
+
+        Self {
+            labels,
+            sw: stopwatch::Stopwatch::start_new(),
+            time_to_headers: None,
+            messages_received: Arc::new(AtomicU64::new(0)),
+            messages_sent: 0,
+            trace_id,
+            finished: false,
+        }
+    }
+
+    pub fn wrap_request_body(&self, body: Body) -> Body {
+        Body::new(GrpcRequestMetricsBody {
+            inner: body,
+            message_counter: GrpcMessageCounter::default(),
+            messages_received: self.messages_received.clone(),
+            bytes_received: metrics::counter!("grpc_bytes_received", &self.labels.get_labels()),
+        })
+    }
+
+    pub fn set_headers_sent(&mut self) {
+        self.time_to_headers = Some(self.sw.elapsed());
+    }
+
+    pub fn finish(mut self, grpc_code: &'static str) {
+        self.finished = true;
+
+        let labels = self.labels.get_labels();
+        let labels_with_code = self.labels.get_labels_with_code(grpc_code);
+        let stream_duration = self.sw.elapsed();
+        let time_to_headers = self.time_to_headers.unwrap_or(stream_duration);
+
+        metrics::histogram!("grpc_request_duration_sec", &labels_with_code)
+            .record(time_to_headers.as_secs_f64());
+        if let Some(trace_id) = self.trace_id.as_ref() {
+            set_histogram_exemplar(
+                "grpc_request_duration_sec",
+                &labels_with_code,
+                time_to_headers.as_secs_f64(),
+                trace_id,
+            );
+        }
+        metrics::counter!("grpc_request_duration_milis_sum", &labels_with_code)
+            .increment(time_to_headers.as_millis() as u64);
+        metrics::counter!("grpc_request_count", &labels_with_code).increment(1);
+
+        metrics::histogram!("grpc_stream_duration_sec", &labels_with_code)
+            .record(stream_duration.as_secs_f64());
+        metrics::histogram!("grpc_stream_messages_sent", &labels).record(self.messages_sent as f64);
+        metrics::histogram!("grpc_stream_messages_received", &labels)
+            .record(self.messages_received.load(Ordering::Relaxed) as f64);
+        metrics::gauge!("grpc_streams_in_flight", &labels).decrement(1);
+    }
+}
+
+impl Drop for GrpcStreamRecorder {
+    fn drop(&mut self) {
+        if !self.finished {
+            metrics::gauge!("grpc_streams_in_flight", &self.labels.get_labels()).decrement(1);
+        }
+    }
+}
+
+/// Counts gRPC messages by following the 5-byte length prefix of every message on the wire.
+#[derive(Default)]
+struct GrpcMessageCounter {
+    header: [u8; 5],
+    header_read: usize,
+    payload_left: usize,
+}
+
+impl GrpcMessageCounter {
+    fn feed(&mut self, mut data: &[u8]) -> u64 {
+        let mut messages = 0;
+
+        while !data.is_empty() {
+            if self.payload_left > 0 {
+                let skip = self.payload_left.min(data.len());
+                self.payload_left -= skip;
+                data = &data[skip..];
+                continue;
+            }
+
+            self.header[self.header_read] = data[0];
+            self.header_read += 1;
+            data = &data[1..];
+
+            if self.header_read == self.header.len() {
+                let len = u32::from_be_bytes([
+                    self.header[1],
+                    self.header[2],
+                    self.header[3],
+                    self.header[4],
+                ]);
+                self.payload_left = len as usize;
+                self.header_read = 0;
+                messages += 1;
+            }
+        }
+
+        messages
+    }
+}
+
+struct GrpcRequestMetricsBody {
+    inner: Body,
+    message_counter: GrpcMessageCounter,
+    messages_received: Arc<AtomicU64>,
+    bytes_received: metrics::Counter,
+}
+
+impl HttpBody for GrpcRequestMetricsBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = &mut *self;
+        let result = Pin::new(&mut this.inner).poll_frame(cx);
+
+        if let Poll::Ready(Some(Ok(frame))) = &result {
+            if let Some(data) = frame.data_ref() {
+                this.bytes_received.increment(data.len() as u64);
+                let messages = this.message_counter.feed(data);
+                this.messages_received
+                    .fetch_add(messages, Ordering::Relaxed);
+            }
+        }
+
+        result
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Response body wrapper which reads `grpc-status` from the trailers once the stream is over.
+pub(crate) struct GrpcMetricsBody {
+    inner: Body,
+    recorder: Option<GrpcStreamRecorder>,
+    message_counter: GrpcMessageCounter,
+    bytes_sent: metrics::Counter,
+}
+
+impl GrpcMetricsBody {
+    pub fn new(inner: Body, recorder: GrpcStreamRecorder) -> Self {
+        let bytes_sent = metrics::counter!("grpc_bytes_sent", &recorder.labels.get_labels());
+        Self {
+            inner,
+            recorder: Some(recorder),
+            message_counter: GrpcMessageCounter::default(),
+            bytes_sent,
+        }
+    }
+
+    fn finish(&mut self, grpc_code: &'static str) {
+        if let Some(recorder) = self.recorder.take() {
+            recorder.finish(grpc_code);
+        }
+    }
+}
+
+impl HttpBody for GrpcMetricsBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let result = Pin::new(&mut self.inner).poll_frame(cx);
+
+        match &result {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    self.bytes_sent.increment(data.len() as u64);
+                    let messages = self.message_counter.feed(data);
+                    if let Some(recorder) = self.recorder.as_mut() {
+                        recorder.messages_sent += messages;
+                    }
+                }
+
+                if let Some(trailers) = frame.trailers_ref() {
+                    let grpc_code = get_grpc_code(trailers).unwrap_or("UNKNOWN");
+                    self.finish(grpc_code);
+                }
+            }
+            Poll::Ready(Some(Err(status))) => {
+                self.finish(get_grpc_code_name(status.code() as i32));
+            }
+            Poll::Ready(None) => {
+                self.finish("UNKNOWN");
+            }
+            Poll::Pending => {}
+        }
+
+        result
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.recorder.is_none() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for GrpcMetricsBody {
+    fn drop(&mut self) {
+        self.finish("CANCELLED");
+    }
+}
+
+pub(crate) fn get_grpc_code(headers: &HeaderMap) -> Option<&'static str> {
+    let grpc_status = headers.get("grpc-status")?;
+    let grpc_status = grpc_status.to_str().ok()?.parse::<i32>().ok()?;
+    Some(get_grpc_code_name(grpc_status))
+}
+
+fn get_grpc_code_name(grpc_status: i32) -> &'static str {
+    match grpc_status {
+        0 => "OK",
+        1 => "CANCELLED",
+        2 => "UNKNOWN",
+        3 => "INVALID_ARGUMENT",
+        4 => "DEADLINE_EXCEEDED",
+        5 => "NOT_FOUND",
+        6 => "ALREADY_EXISTS",
+        7 => "PERMISSION_DENIED",
+        8 => "RESOURCE_EXHAUSTED",
+        9 => "FAILED_PRECONDITION",
+        10 => "ABORTED",
+        11 => "OUT_OF_RANGE",
+        12 => "UNIMPLEMENTED",
+        13 => "INTERNAL",
+        14 => "UNAVAILABLE",
+        15 => "DATA_LOSS",
+        16 => "UNAUTHENTICATED",
+        _ => "UNKNOWN",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_known_services() -> HashSet<String> {
+        HashSet::from(["keyvalue.KeyValueService".to_string()])
+    }
+
+    #[test]
+    fn test_from_path_of_known_service() {
+        let labels =
+            GrpcCallLabels::from_path("/keyvalue.KeyValueService/Get", &get_known_services());
+
+        assert_eq!(labels.grpc_service, "keyvalue.KeyValueService");
+        assert_eq!(labels.grpc_method, "Get");
+    }
+
+    #[test]
+    fn test_from_path_of_unknown_service() {
+        let known_services = get_known_services();
+
+        for path in [
+            "/scanner.Probe/Get",
+            "/keyvalue.KeyValueService",
+            "/keyvalue.KeyValueService/",
+            "/",
+            "",
+        ] {
+            let labels = GrpcCallLabels::from_path(path, &known_services);
+            assert_eq!(labels.grpc_service, "unknown", "{}", path);
+            assert_eq!(labels.grpc_method, "unknown", "{}", path);
+        }
+    }
+
+    fn get_header_map(grpc_status: &'static str) -> HeaderMap {
+        let mut result = HeaderMap::new();
+        result.insert("content-type", "application/grpc".parse().unwrap());
+        result.insert("grpc-status", grpc_status.parse().unwrap());
+        result
+    }
+
+    #[test]
+    fn test_grpc_code_from_trailers_only_headers() {
+        assert_eq!(get_grpc_code(&get_header_map("0")), Some("OK"));
+        assert_eq!(get_grpc_code(&get_header_map("12")), Some("UNIMPLEMENTED"));
+    }
+
+    #[test]
+    fn test_grpc_code_from_trailers() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", "5".parse().unwrap());
+        trailers.insert("grpc-message", "not found".parse().unwrap());
+
+        assert_eq!(get_grpc_code(&trailers), Some("NOT_FOUND"));
+    }
+
+    #[test]
+    fn test_grpc_code_is_missing_or_invalid() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/grpc".parse().unwrap());
+        assert_eq!(get_grpc_code(&headers), None);
+
+        assert_eq!(get_grpc_code(&get_header_map("abc")), None);
+        assert_eq!(get_grpc_code(&get_header_map("99")), Some("UNKNOWN"));
+    }
+
+    fn get_message(payload_len: usize) -> Vec<u8> {
+        let mut result = vec![0];
+        result.extend_from_slice(&(payload_len as u32).to_be_bytes());
+        result.resize(result.len() + payload_len, 7);
+        result
+    }
+
+    #[test]
+    fn test_feed_with_prefix_split_across_chunks() {
+        let message = get_message(10);
+        let mut counter = GrpcMessageCounter::default();
+
+        assert_eq!(counter.feed(&message[..2]), 0);
+        assert_eq!(counter.feed(&message[2..5]), 1);
+        assert_eq!(counter.feed(&message[5..9]), 0);
+        assert_eq!(counter.feed(&message[9..]), 0);
+
+        let message = get_message(3);
+        assert_eq!(counter.feed(&message[..4]), 0);
+        assert_eq!(counter.feed(&message[4..]), 1);
+    }
+
+    #[test]
+    fn test_feed_with_several_messages_in_one_chunk() {
+        let mut chunk = get_message(3);
+        chunk.extend(get_message(0));
+        chunk.extend(get_message(20));
+
+        let next_message = get_message(4);
+        chunk.extend_from_slice(&next_message[..3]);
+
+        let mut counter = GrpcMessageCounter::default();
+        assert_eq!(counter.feed(&chunk), 3);
+        assert_eq!(counter.feed(&next_message[3..]), 1);
+    }
+
+    #[test]
+    fn test_feed_with_empty_frames() {
+        let mut counter = GrpcMessageCounter::default();
+        assert_eq!(counter.feed(&[]), 0);
+
+        let message = get_message(0);
+        assert_eq!(counter.feed(&message), 1);
+        assert_eq!(counter.feed(&[]), 0);
+
+        let message = get_message(2);
+        assert_eq!(counter.feed(&message[..5]), 1);
+        assert_eq!(counter.feed(&[]), 0);
+        assert_eq!(counter.feed(&message[5..]), 0);
+        assert_eq!(counter.feed(&get_message(0)), 1);
+    }
+}
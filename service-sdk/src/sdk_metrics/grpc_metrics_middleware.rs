@@ -1,24 +1,52 @@
+use std::collections::HashSet;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
 use my_grpc_extensions::hyper;
 use my_grpc_extensions::tonic::body::Body;
+use my_grpc_extensions::tonic::metadata::MetadataMap;
 use tower::{Layer, Service};
 
+use super::get_trace_id;
+use super::grpc_metrics_body::{
+    get_grpc_code, GrpcCallLabels, GrpcMetricsBody, GrpcStreamRecorder,
+};
+
+/// Calls of services other than `known_services` are recorded with `unknown` service and method labels.
 #[derive(Debug, Clone, Default)]
-pub struct GrpcMetricsMiddlewareLayer;
+pub struct GrpcMetricsMiddlewareLayer {
+    known_services: Arc<HashSet<String>>,
+}
+
+impl GrpcMetricsMiddlewareLayer {
+    pub fn new<'s>(known_services: impl IntoIterator<Item = &'s str>) -> Self {
+        Self {
+            known_services: Arc::new(
+                known_services
+                    .into_iter()
+                    .map(|itm| itm.to_string())
+                    .collect(),
+            ),
+        }
+    }
+}
 
 impl<S> Layer<S> for GrpcMetricsMiddlewareLayer {
     type Service = GrpcMetricsMiddleware<S>;
 
     fn layer(&self, service: S) -> Self::Service {
-        GrpcMetricsMiddleware { inner: service }
+        GrpcMetricsMiddleware {
+            inner: service,
+            known_services: self.known_services.clone(),
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct GrpcMetricsMiddleware<S> {
     inner: S,
+    known_services: Arc<HashSet<String>>,
 }
 
 type BoxFuture<'a, T> = Pin<Box<dyn std::future::Future<Output = T> + Send + 'a>>;
@@ -39,27 +67,27 @@ where
     fn call(&mut self, req: hyper::Request<Body>) -> Self::Future {
         let clone = self.inner.clone();
         let mut inner = std::mem::replace(&mut self.inner, clone);
-        let method = req.method().to_string();
-        let path = req.uri().path().to_string();
+        let labels = GrpcCallLabels::from_path(req.uri().path(), &self.known_services);
+        // The same telemetry context the generated services read, so the trace id matches theirs.
+        let telemetry = my_grpc_extensions::server::get_telemetry(
+            &MetadataMap::from_headers(req.headers().clone()),
+            None,
+            req.uri().path(),
+        );
+        let mut recorder = GrpcStreamRecorder::start(labels, get_trace_id(&telemetry));
+        let req = req.map(|body| recorder.wrap_request_body(body));
 
         Box::pin(async move {
-            let mut sw = stopwatch::Stopwatch::start_new();
             let response = inner.call(req).await?;
-            sw.stop();
-            let duration = sw.elapsed();
-            let common_labels = &[
-                ("method", method),
-                ("path", path),
-                ("status_code", response.status().to_string()),
-            ];
+            recorder.set_headers_sent();
 
-            metrics::histogram!("grpc_request_duration_sec", common_labels)
-                .record(duration.as_secs_f64());
-            metrics::counter!("grpc_request_duration_milis_sum", common_labels)
-                .increment(duration.as_millis() as u64);
-            metrics::counter!("grpc_request_count", common_labels).increment(1);
+            // Trailers-only response: the status is already in the headers.
+            if let Some(grpc_code) = get_grpc_code(response.headers()) {
+                recorder.finish(grpc_code);
+                return Ok(response);
+            }
 
-            Ok(response)
+            Ok(response.map(|body| Body::new(GrpcMetricsBody::new(body, recorder))))
         })
     }
 }
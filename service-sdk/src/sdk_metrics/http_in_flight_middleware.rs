@@ -0,0 +1,119 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use my_http_server::*;
+use tokio::sync::Notify;
+
+use super::HttpMetricsRoutes;
+
+/// Runs the SDK middlewares of a listener while the request is counted in `http_requests_in_flight`.
+/// The count is released by a guard, so requests that panic or are dropped with the connection are not leaked.
+/// `MetricsTechMiddleware` records the result.
+pub struct MetricsInFlightMiddleware {
+    routes: Arc<HttpMetricsRoutes>,
+    in_flight_requests: Arc<HttpInFlightRequests>,
+    middlewares: Vec<Arc<dyn HttpServerMiddleware + Send + Sync + 'static>>,
+}
+
+impl MetricsInFlightMiddleware {
+    pub(crate) fn new(
+        routes: Arc<HttpMetricsRoutes>,
+        in_flight_requests: Arc<HttpInFlightRequests>,
+        middlewares: Vec<Arc<dyn HttpServerMiddleware + Send + Sync + 'static>>,
+    ) -> Self {
+        Self {
+            routes,
+            in_flight_requests,
+            middlewares,
+        }
+    }
+
+    fn record_request_size(&self, ctx: &HttpContext) {
+        let Some(path) = self.routes.get_metrics_path(ctx.request.http_path.as_str()) else {
+            return;
+        };
+
+        // Chunked and streamed bodies have no length up front: they are not observed rather than seen as empty.
+        let Some(request_size) = ctx
+            .request
+            .get_headers()
+            .try_get_case_insensitive("content-length")
+            .and_then(|header| header.as_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+        else {
+            return;
+        };
+
+        let labels = &[
+            ("method", ctx.request.method.as_str().to_string()),
+            ("path", path),
+        ];
+
+        metrics::histogram!("http_request_size_bytes", labels).record(request_size as f64);
+    }
+}
+
+#[async_trait]
+impl HttpServerMiddleware for MetricsInFlightMiddleware {
+    async fn handle_request(
+        &self,
+        ctx: &mut HttpContext,
+    ) -> Option<Result<HttpOkResult, HttpFailResult>> {
+        let _in_flight = InFlightGuard::new(self.in_flight_requests.clone());
+
+        self.record_request_size(ctx);
+
+        for middleware in self.middlewares.iter() {
+            if let Some(result) = middleware.handle_request(ctx).await {
+                return Some(result);
+            }
+        }
+
+        None
+    }
+}
+
+/// Requests being handled by every listener of the HTTP server. Graceful shutdown waits for them.
+#[derive(Default)]
+pub(crate) struct HttpInFlightRequests {
+    count: AtomicUsize,
+    notify: Notify,
+}
+
+impl HttpInFlightRequests {
+    pub async fn wait_until_empty(&self) {
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if self.count.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+
+            notified.await;
+        }
+    }
+}
+
+struct InFlightGuard {
+    in_flight_requests: Arc<HttpInFlightRequests>,
+}
+
+impl InFlightGuard {
+    fn new(in_flight_requests: Arc<HttpInFlightRequests>) -> Self {
+        in_flight_requests.count.fetch_add(1, Ordering::SeqCst);
+        metrics::gauge!("http_requests_in_flight").increment(1);
+        Self { in_flight_requests }
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        metrics::gauge!("http_requests_in_flight").decrement(1);
+        if self.in_flight_requests.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.in_flight_requests.notify.notify_waiters();
+        }
+    }
+}
@@ -1,6 +1,11 @@
 use async_trait::async_trait;
 use my_http_server::*;
 
+use super::MetricsFormat;
+
+pub const METRICS_PATH: &str = "/metrics";
+pub const JSON_METRICS_PATH: &str = "/metrics/json";
+
 pub struct MetricsMiddleware;
 
 impl MetricsMiddleware {
@@ -21,70 +26,52 @@ impl HttpServerMiddleware for MetricsMiddleware {
         &self,
         ctx: &mut HttpContext,
     ) -> Option<Result<HttpOkResult, HttpFailResult>> {
-        let path = ctx.request.http_path.as_str().to_string();
+        let headers = ctx.request.get_headers();
+        let get_header = |name: &str| {
+            headers
+                .try_get_case_insensitive(name)
+                .and_then(|header| header.as_str().ok())
+                .map(|value| value.to_string())
+        };
 
-        if path == "/metrics" {
-            let report = prometheus::TextEncoder::new()
-                .encode_to_string(&prometheus::default_registry().gather());
+        let format = match ctx.request.http_path.as_str() {
+            METRICS_PATH => MetricsFormat::from_accept_header(get_header("accept").as_deref()),
+            JSON_METRICS_PATH => MetricsFormat::Json,
+            _ => return None,
+        };
 
-            match report {
-                Ok(report) => {
-                    let response = HttpOutput::as_text(report).into_ok_result(false);
-                    return Some(response);
-                }
-                Err(err) => {
-                    let response = HttpOutput::from_builder()
-                        .set_content_as_text(err.to_string())
-                        .set_status_code(502)
-                        .into_err(false, false);
+        let gzip = super::accepts_gzip(get_header("accept-encoding").as_deref());
 
-                    return Some(response);
+        let content = format
+            .render(&prometheus::default_registry().gather())
+            .and_then(|report| {
+                if gzip {
+                    super::gzip(report.as_bytes())
+                } else {
+                    Ok(report.into_bytes())
                 }
-            }
-        }
-        None
-
-        /*
-        let mut sw = Stopwatch::start_new();
-        let result = get_next.next(ctx).await;
-        sw.stop();
+            });
 
-        let duration = sw.elapsed();
-        let method = ctx.request.method.as_str().to_string();
-        let path = ctx.request.http_path.as_str().to_string();
-        let common_labels = &[("method", method.clone()), ("path", path.clone())];
+        match content {
+            Ok(content) => {
+                let mut builder = HttpOutput::from_builder()
+                    .add_header("content-type", format.get_content_type());
 
-        let mut has_to_write_metrics = false;
-
-        if let Err(result) = &result {
-            if result.status_code == 404 {
-                has_to_write_metrics = true
-            } else {
-                let failed_labels = &[
-                    ("method", method.clone()),
-                    ("path", path.clone()),
-                    ("status_code", result.status_code.to_string()),
-                ];
+                if gzip {
+                    builder = builder.add_header("content-encoding", "gzip");
+                }
 
-                metrics::counter!("http_failed_request_count", failed_labels).increment(1);
-                metrics::counter!("http_failed_request_milis_duration_sum", failed_labels)
-                    .increment(duration.as_millis() as u64);
-                metrics::histogram!("http_failed_request_duration_sec", failed_labels)
-                    .record(duration.as_secs_f64());
+                let response = builder.set_content(content).into_ok_result(false);
+                Some(response)
             }
-        }
+            Err(err) => {
+                let response = HttpOutput::from_builder()
+                    .set_content_as_text(err)
+                    .set_status_code(502)
+                    .into_err(false, false);
 
-        if has_to_write_metrics {
-            return result;
+                Some(response)
+            }
         }
-
-        metrics::histogram!("http_request_duration_sec", common_labels)
-            .record(duration.as_secs_f64());
-        metrics::counter!("http_request_milis_duration_sum", common_labels)
-            .increment(duration.as_millis() as u64);
-        metrics::counter!("http_request_count", common_labels).increment(1);
-
-        return result;
-         */
     }
 }
@@ -0,0 +1,259 @@
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+pub const UNMATCHED_HTTP_PATH: &str = "unmatched";
+
+enum RouteSegment {
+    Static(String),
+    Param,
+    Wildcard,
+}
+
+struct RouteTemplate {
+    template: String,
+    segments: Vec<RouteSegment>,
+}
+
+impl RouteTemplate {
+    fn new(template: &str) -> Self {
+        let segments = split_path(template)
+            .map(|segment| {
+                if segment == "*" {
+                    RouteSegment::Wildcard
+                } else if segment.starts_with('{') && segment.ends_with('}') {
+                    RouteSegment::Param
+                } else {
+                    RouteSegment::Static(segment.to_lowercase())
+                }
+            })
+            .collect();
+
+        Self {
+            template: template.to_string(),
+            segments,
+        }
+    }
+
+    // Number of static segments matched. Static routes win over the ones with params.
+    fn get_match_score(&self, path: &[&str]) -> Option<usize> {
+        let mut score = 0;
+
+        for (index, segment) in self.segments.iter().enumerate() {
+            match segment {
+                RouteSegment::Wildcard => return Some(score),
+                RouteSegment::Param => {
+                    if index >= path.len() {
+                        return None;
+                    }
+                }
+                RouteSegment::Static(value) => {
+                    if !path.get(index)?.eq_ignore_ascii_case(value) {
+                        return None;
+                    }
+                    score += 1;
+                }
+            }
+        }
+
+        if self.segments.len() == path.len() {
+            Some(score)
+        } else {
+            None
+        }
+    }
+}
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+/// Route templates the `path` label of HTTP metrics is resolved to.
+/// Requests which match none of them are labeled as `unmatched`.
+pub(crate) struct HttpMetricsRoutes {
+    templates: ArcSwap<Vec<Arc<RouteTemplate>>>,
+    allow_list: ArcSwap<Vec<String>>,
+    deny_list: ArcSwap<Vec<String>>,
+}
+
+impl HttpMetricsRoutes {
+    pub fn new() -> Self {
+        Self {
+            templates: ArcSwap::from_pointee(Vec::new()),
+            allow_list: ArcSwap::from_pointee(Vec::new()),
+            deny_list: ArcSwap::from_pointee(Vec::new()),
+        }
+    }
+
+    /// `{param}` matches a single segment, a trailing `*` matches the rest of the path.
+    pub fn register_route(&self, template: &str) {
+        let template = Arc::new(RouteTemplate::new(template));
+        self.templates.rcu(|templates| {
+            if templates
+                .iter()
+                .any(|itm| itm.template == template.template)
+            {
+                return templates.clone();
+            }
+
+            let mut result = Vec::with_capacity(templates.len() + 1);
+            result.extend(templates.iter().cloned());
+            result.push(template.clone());
+            Arc::new(result)
+        });
+    }
+
+    /// Only these routes are recorded. Empty list means all of them.
+    pub fn set_allow_list(&self, routes: &[&str]) {
+        self.allow_list
+            .store(Arc::new(routes.iter().map(|itm| itm.to_string()).collect()));
+    }
+
+    /// These routes are never recorded. `unmatched` can be listed as well.
+    pub fn set_deny_list(&self, routes: &[&str]) {
+        self.deny_list
+            .store(Arc::new(routes.iter().map(|itm| itm.to_string()).collect()));
+    }
+
+    pub fn get_route(&self, path: &str) -> String {
+        let path: Vec<&str> = split_path(path).collect();
+
+        let mut result: Option<(usize, &RouteTemplate)> = None;
+        let templates = self.templates.load();
+
+        for template in templates.iter() {
+            if let Some(score) = template.get_match_score(&path) {
+                let is_better = match result {
+                    Some((best_score, _)) => score > best_score,
+                    None => true,
+                };
+
+                if is_better {
+                    result = Some((score, template.as_ref()));
+                }
+            }
+        }
+
+        match result {
+            Some((_, template)) => template.template.clone(),
+            None => UNMATCHED_HTTP_PATH.to_string(),
+        }
+    }
+
+    /// Resolves the `path` label. `None` means the request is excluded from metrics.
+    pub fn get_metrics_path(&self, path: &str) -> Option<String> {
+        let route = self.get_route(path);
+
+        if self.deny_list.load().iter().any(|itm| itm == &route) {
+            return None;
+        }
+
+        let allow_list = self.allow_list.load();
+        if !allow_list.is_empty() && !allow_list.iter().any(|itm| itm == &route) {
+            return None;
+        }
+
+        Some(route)
+    }
+}
+
+impl Default for HttpMetricsRoutes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_routes(templates: &[&str]) -> HttpMetricsRoutes {
+        let routes = HttpMetricsRoutes::new();
+        for template in templates {
+            routes.register_route(template);
+        }
+        routes
+    }
+
+    #[test]
+    fn test_static_segments_win() {
+        let routes = get_routes(&[
+            "/api/users/{id}",
+            "/api/users/me",
+            "/api/{version}/users/{id}",
+            "/api/*",
+        ]);
+
+        assert_eq!(routes.get_route("/api/users/me"), "/api/users/me");
+        assert_eq!(routes.get_route("/api/users/15"), "/api/users/{id}");
+        assert_eq!(
+            routes.get_route("/api/v2/users/15"),
+            "/api/{version}/users/{id}"
+        );
+        assert_eq!(routes.get_route("/api/orders/15"), "/api/*");
+    }
+
+    #[test]
+    fn test_first_registered_route_wins_a_tie() {
+        let routes = get_routes(&["/api/{entity}/15", "/api/users/{id}"]);
+
+        assert_eq!(routes.get_route("/api/users/15"), "/api/{entity}/15");
+    }
+
+    #[test]
+    fn test_trailing_and_double_slashes_are_ignored() {
+        let routes = get_routes(&["/api/users/{id}/"]);
+
+        assert_eq!(routes.get_route("/api/users/15"), "/api/users/{id}/");
+        assert_eq!(routes.get_route("/api/users/15/"), "/api/users/{id}/");
+        assert_eq!(routes.get_route("//api//users/15"), "/api/users/{id}/");
+    }
+
+    #[test]
+    fn test_static_segments_ignore_case() {
+        let routes = get_routes(&["/Api/Users/{id}"]);
+
+        assert_eq!(routes.get_route("/api/USERS/15"), "/Api/Users/{id}");
+    }
+
+    #[test]
+    fn test_params_match_exactly_one_segment() {
+        let routes = get_routes(&["/api/users/{id}"]);
+
+        assert_eq!(routes.get_route("/api/users"), UNMATCHED_HTTP_PATH);
+        assert_eq!(
+            routes.get_route("/api/users/15/orders"),
+            UNMATCHED_HTTP_PATH
+        );
+    }
+
+    #[test]
+    fn test_deny_wins_over_allow() {
+        let routes = get_routes(&["/api/users/{id}", "/api/orders/{id}"]);
+        routes.set_allow_list(&["/api/users/{id}", "/api/orders/{id}"]);
+        routes.set_deny_list(&["/api/orders/{id}"]);
+
+        assert_eq!(
+            routes.get_metrics_path("/api/users/15"),
+            Some("/api/users/{id}".to_string())
+        );
+        assert_eq!(routes.get_metrics_path("/api/orders/15"), None);
+    }
+
+    #[test]
+    fn test_unmatched_paths() {
+        let routes = get_routes(&["/api/users/{id}"]);
+
+        assert_eq!(
+            routes.get_metrics_path("/metrics"),
+            Some(UNMATCHED_HTTP_PATH.to_string())
+        );
+
+        routes.set_deny_list(&[UNMATCHED_HTTP_PATH]);
+        assert_eq!(routes.get_metrics_path("/metrics"), None);
+
+        routes.set_deny_list(&[]);
+        routes.set_allow_list(&["/api/users/{id}"]);
+        assert_eq!(routes.get_metrics_path("/metrics"), None);
+    }
+}
@@ -1,40 +1,285 @@
+use std::sync::Arc;
+
 use async_trait::async_trait;
 use my_http_server::*;
 use rust_extensions::date_time::DateTimeAsMicroseconds;
 
-pub struct MetricsTechMiddleware;
+use super::{get_trace_id, HttpMetricsRoutes, HttpRequestMetrics};
+
+pub struct MetricsTechMiddleware {
+    routes: Arc<HttpMetricsRoutes>,
+}
+
+impl MetricsTechMiddleware {
+    pub(crate) fn new(routes: Arc<HttpMetricsRoutes>) -> Self {
+        Self { routes }
+    }
+}
 
 #[async_trait]
 impl HttpServerTechMiddleware for MetricsTechMiddleware {
     async fn got_result(&self, request: &HttpRequestData, http_result: &ResponseData) {
+        let Some(path) = self.routes.get_metrics_path(request.path.as_str()) else {
+            return;
+        };
+
         let now = DateTimeAsMicroseconds::now();
-        let duration = now.duration_since(request.started).as_positive_or_zero();
 
-        let common_labels = &[
-            ("method", request.method.to_string()),
-            ("path", request.path.to_string()),
+        HttpRequestMetrics {
+            method: request.method.to_string(),
+            path,
+            status_code: http_result.status_code,
+            duration: now.duration_since(request.started).as_positive_or_zero(),
+            response_size: http_result.content_length as u64,
+            trace_id: get_trace_id(&request.telemetry_context),
+        }
+        .record();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::time::Duration;
+
+    use arc_swap::ArcSwap;
+    use rust_extensions::AppStates;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    use super::*;
+    use crate::{
+        HistogramBuckets, HttpServerBuilder, MetricsConfig, MetricsFormat, SdkMetricsRecorder,
+    };
+
+    struct UsersMiddleware;
+
+    #[async_trait]
+    impl HttpServerMiddleware for UsersMiddleware {
+        async fn handle_request(
+            &self,
+            ctx: &mut HttpContext,
+        ) -> Option<Result<HttpOkResult, HttpFailResult>> {
+            let status_code = match ctx.request.http_path.as_str() {
+                "/api/users/15" => {
+                    let response = HttpOutput::from_builder()
+                        .set_content_as_text("user")
+                        .into_ok_result(false);
+                    return Some(response);
+                }
+                "/api/users/16" => 400,
+                "/api/users/17" => 500,
+                _ => 404,
+            };
+
+            let response = HttpOutput::from_builder()
+                .set_status_code(status_code)
+                .set_content_as_text("error")
+                .into_err(false, false);
+
+            Some(response)
+        }
+    }
+
+    async fn send_get(addr: SocketAddr, path: &str) -> String {
+        let mut tcp_stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            path
+        );
+        tcp_stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = vec![];
+        tcp_stream.read_to_end(&mut response).await.unwrap();
+        String::from_utf8(response).unwrap()
+    }
+
+    // Durations depend on the machine: their sums are replaced with `*`.
+    fn mask_durations(report: &str) -> String {
+        report
+            .lines()
+            .map(|line| {
+                let name = line.split(['{', ' ']).next().unwrap_or_default();
+                if !name.ends_with("_duration_sec_sum") && !name.ends_with("_milis_duration_sum") {
+                    return format!("{}\n", line);
+                }
+
+                match line.rsplit_once(' ') {
+                    Some((series, _)) => format!("{} *\n", series),
+                    None => format!("{}\n", line),
+                }
+            })
+            .collect()
+    }
+
+    fn get_expected_report() -> String {
+        let users_200 = r#"env="test",method="GET",path="/api/users/{id}",status_class="2xx""#;
+        let users_400 = r#"env="test",method="GET",path="/api/users/{id}",status_code="400""#;
+        let users_500 = r#"env="test",method="GET",path="/api/users/{id}",status_code="500""#;
+        let unmatched_404 = r#"env="test",method="GET",path="unmatched",status_code="404""#;
+        let users_4xx = r#"env="test",method="GET",path="/api/users/{id}",status_class="4xx""#;
+        let users_5xx = r#"env="test",method="GET",path="/api/users/{id}",status_class="5xx""#;
+        let unmatched_4xx = r#"env="test",method="GET",path="unmatched",status_class="4xx""#;
+
+        let mut lines = vec![
+            "# HELP sdk_test_http_failed_request_count sdk_test_http_failed_request_count"
+                .to_string(),
+            "# TYPE sdk_test_http_failed_request_count counter".to_string(),
         ];
+        for labels in [users_400, users_500, unmatched_404] {
+            lines.push(format!(
+                "sdk_test_http_failed_request_count{{{}}} 1",
+                labels
+            ));
+        }
+
+        lines.push("# HELP sdk_test_http_failed_request_duration_sec sdk_test_http_failed_request_duration_sec".to_string());
+        lines.push("# TYPE sdk_test_http_failed_request_duration_sec histogram".to_string());
+        for labels in [users_400, users_500, unmatched_404] {
+            lines.extend(get_histogram_lines(
+                "sdk_test_http_failed_request_duration_sec",
+                labels,
+                "60",
+                1,
+                "*",
+            ));
+        }
+
+        lines.push("# HELP sdk_test_http_failed_request_milis_duration_sum sdk_test_http_failed_request_milis_duration_sum".to_string());
+        lines.push("# TYPE sdk_test_http_failed_request_milis_duration_sum counter".to_string());
+        for labels in [users_400, users_500, unmatched_404] {
+            lines.push(format!(
+                "sdk_test_http_failed_request_milis_duration_sum{{{}}} *",
+                labels
+            ));
+        }
+
+        lines.extend([
+            "# HELP sdk_test_http_request_count sdk_test_http_request_count".to_string(),
+            "# TYPE sdk_test_http_request_count counter".to_string(),
+            format!("sdk_test_http_request_count{{{}}} 2", users_200),
+            "# HELP sdk_test_http_request_duration_sec sdk_test_http_request_duration_sec"
+                .to_string(),
+            "# TYPE sdk_test_http_request_duration_sec histogram".to_string(),
+        ]);
+        lines.extend(get_histogram_lines(
+            "sdk_test_http_request_duration_sec",
+            users_200,
+            "60",
+            2,
+            "*",
+        ));
+
+        lines.extend([
+            "# HELP sdk_test_http_request_milis_duration_sum sdk_test_http_request_milis_duration_sum".to_string(),
+            "# TYPE sdk_test_http_request_milis_duration_sum counter".to_string(),
+            format!("sdk_test_http_request_milis_duration_sum{{{}}} *", users_200),
+            // Requests are over: none is in flight.
+            "# HELP sdk_test_http_requests_in_flight sdk_test_http_requests_in_flight".to_string(),
+            "# TYPE sdk_test_http_requests_in_flight gauge".to_string(),
+            r#"sdk_test_http_requests_in_flight{env="test"} 0"#.to_string(),
+            "# HELP sdk_test_http_response_size_bytes sdk_test_http_response_size_bytes".to_string(),
+            "# TYPE sdk_test_http_response_size_bytes histogram".to_string(),
+        ]);
+        // `user` for the found user, `error` for the others.
+        lines.extend(get_histogram_lines(
+            "sdk_test_http_response_size_bytes",
+            users_200,
+            "10",
+            2,
+            "8",
+        ));
+        for labels in [users_4xx, users_5xx, unmatched_4xx] {
+            lines.extend(get_histogram_lines(
+                "sdk_test_http_response_size_bytes",
+                labels,
+                "10",
+                1,
+                "5",
+            ));
+        }
 
-        if http_result.has_error {
-            if http_result.status_code == 404 {
-                metrics::histogram!("http_request_duration_sec", common_labels)
-                    .record(duration.as_secs_f64());
-                metrics::counter!("http_request_milis_duration_sum", common_labels)
-                    .increment(duration.as_millis() as u64);
-                metrics::counter!("http_request_count", common_labels).increment(1);
-            } else {
-                let failed_labels = &[
-                    ("method", request.method.to_string()),
-                    ("path", request.path.to_string()),
-                    ("status_code", http_result.status_code.to_string()),
-                ];
-
-                metrics::counter!("http_failed_request_count", failed_labels).increment(1);
-                metrics::counter!("http_failed_request_milis_duration_sum", failed_labels)
-                    .increment(duration.as_millis() as u64);
-                metrics::histogram!("http_failed_request_duration_sec", failed_labels)
-                    .record(duration.as_secs_f64());
+        // GET requests have no `content-length`: `http_request_size_bytes` is not there.
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    fn get_histogram_lines(
+        name: &str,
+        labels: &str,
+        bucket: &str,
+        count: u64,
+        sum: &str,
+    ) -> Vec<String> {
+        vec![
+            format!("{}_bucket{{{},le=\"{}\"}} {}", name, labels, bucket, count),
+            format!("{}_bucket{{{},le=\"+Inf\"}} {}", name, labels, count),
+            format!("{}_sum{{{}}} {}", name, labels, sum),
+            format!("{}_count{{{}}} {}", name, labels, count),
+        ]
+    }
+
+    // The server runs on the test thread, so the local recorder gets its metrics and nothing global is changed.
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_every_request_is_recorded_once() {
+        let registry = prometheus::Registry::new();
+        let config: &'static ArcSwap<MetricsConfig> =
+            Box::leak(Box::new(ArcSwap::from_pointee(MetricsConfig {
+                prefix: Some("sdk_test".to_string()),
+                constant_labels: [("env".to_string(), "test".to_string())].into(),
+                histogram_buckets: vec![
+                    HistogramBuckets::new("*_duration_sec", &[60.0]),
+                    HistogramBuckets::new("*_size_bytes", &[10.0]),
+                ],
+            })));
+        let recorder = SdkMetricsRecorder::new(&registry, config);
+        let _recorder_guard = metrics::set_default_local_recorder(&recorder);
+
+        // The port is taken from a listener which is dropped right away: the server binds it again.
+        let addr = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let mut builder = HttpServerBuilder::new("sdk-test", "1.0.0");
+        builder.update_listen_endpoint(addr.ip(), addr.port());
+        builder.register_metrics_route("/api/users/{id}");
+        builder.register_custom_middleware(Arc::new(UsersMiddleware));
+        builder.bind().unwrap();
+
+        let app_states = Arc::new(AppStates::create_initialized());
+        let _http_servers = builder.start(&app_states);
+        builder
+            .wait_until_listening(Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        for path in [
+            "/api/users/15",
+            "/api/users/15",
+            "/api/users/16",
+            "/api/users/17",
+        ] {
+            send_get(addr, path).await;
+        }
+        let response = send_get(addr, "/api/orders/1").await;
+        assert!(response.starts_with("HTTP/1.1 404"));
+
+        // Results are recorded once the response is written, so the report is polled until it has them.
+        let expected = get_expected_report();
+        let mut report = String::new();
+        for _ in 0..50 {
+            report = mask_durations(
+                &MetricsFormat::Prometheus
+                    .render(&registry.gather())
+                    .unwrap(),
+            );
+            if report == expected {
+                break;
             }
+            tokio::time::sleep(Duration::from_millis(100)).await;
         }
+
+        assert_eq!(report, expected);
     }
 }
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use super::set_histogram_exemplar;
+
+/// Finished HTTP request as it is recorded by `MetricsTechMiddleware`.
+/// Every request is recorded once: as a successful one if the status code is below 400, as a failed one otherwise.
+#[derive(Debug, Clone)]
+pub(crate) struct HttpRequestMetrics {
+    pub method: String,
+    /// Route template resolved by `HttpMetricsRoutes`.
+    pub path: String,
+    pub status_code: u16,
+    pub duration: Duration,
+    pub response_size: u64,
+    /// Becomes the exemplar of the duration bucket.
+    pub trace_id: Option<String>,
+}
+
+impl HttpRequestMetrics {
+    pub fn is_failed(&self) -> bool {
+        self.status_code >= 400
+    }
+
+    pub fn get_status_class(&self) -> String {
+        format!("{}xx", self.status_code / 100)
+    }
+
+    pub fn record(&self) {
+        let common_labels = &[
+            ("method", self.method.clone()),
+            ("path", self.path.clone()),
+            ("status_class", self.get_status_class()),
+        ];
+
+        metrics::histogram!("http_response_size_bytes", common_labels)
+            .record(self.response_size as f64);
+
+        if self.is_failed() {
+            let failed_labels = &[
+                ("method", self.method.clone()),
+                ("path", self.path.clone()),
+                ("status_code", self.status_code.to_string()),
+            ];
+
+            metrics::counter!("http_failed_request_count", failed_labels).increment(1);
+            metrics::counter!("http_failed_request_milis_duration_sum", failed_labels)
+                .increment(self.duration.as_millis() as u64);
+            metrics::histogram!("http_failed_request_duration_sec", failed_labels)
+                .record(self.duration.as_secs_f64());
+            self.set_duration_exemplar("http_failed_request_duration_sec", failed_labels);
+            return;
+        }
+
+        metrics::counter!("http_request_count", common_labels).increment(1);
+        metrics::counter!("http_request_milis_duration_sum", common_labels)
+            .increment(self.duration.as_millis() as u64);
+        metrics::histogram!("http_request_duration_sec", common_labels)
+            .record(self.duration.as_secs_f64());
+        self.set_duration_exemplar("http_request_duration_sec", common_labels);
+    }
+
+    fn set_duration_exemplar(&self, metric_name: &str, labels: &[(&'static str, String)]) {
+        if let Some(trace_id) = self.trace_id.as_ref() {
+            set_histogram_exemplar(metric_name, labels, self.duration.as_secs_f64(), trace_id);
+        }
+    }
+}
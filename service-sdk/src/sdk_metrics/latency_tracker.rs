@@ -0,0 +1,626 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use arc_swap::ArcSwap;
+use async_trait::async_trait;
+use rust_extensions::MyTimerTick;
+
+use super::{
+    get_upper_bound, normalize_labels, remove_exemplars, MetricsExemplar, DEFAULT_LABEL_SET_TTL,
+};
+
+type LabelsKey = Vec<(String, String)>;
+
+/// Percentiles are calculated over the observations of the last 60 seconds.
+pub const LATENCY_PERCENTILES_WINDOW: usize = 60;
+
+/// Observations above the limit within one second are left out of percentiles. The histogram gets all of them.
+pub const MAX_LATENCY_SAMPLES_PER_SECOND: usize = 10_000;
+
+const PERCENTILES: [(&str, f64); 3] = [("p50", 0.5), ("p90", 0.9), ("p99", 0.99)];
+
+struct CurrentSamples {
+    values: Vec<f64>,
+    // Set by the timer once the label set is removed. Observations go to a fresh label set then.
+    evicted: bool,
+}
+
+struct WindowState {
+    window: VecDeque<Vec<f64>>,
+    idle_ticks: u64,
+}
+
+struct LatencySamples {
+    histogram: prometheus::Histogram,
+    current: Mutex<CurrentSamples>,
+    // Accessed only by the timer.
+    state: Mutex<WindowState>,
+}
+
+impl LatencySamples {
+    fn new(histogram: prometheus::Histogram) -> Self {
+        Self {
+            histogram,
+            current: Mutex::new(CurrentSamples {
+                values: Vec::new(),
+                evicted: false,
+            }),
+            state: Mutex::new(WindowState {
+                window: VecDeque::new(),
+                idle_ticks: 0,
+            }),
+        }
+    }
+
+    // The histogram and its exemplar are observed under the lock, so nothing is observed into a removed series.
+    fn observe(&self, value: f64, set_exemplar: impl FnOnce()) -> bool {
+        let mut current = self.current.lock().unwrap();
+        if current.evicted {
+            return false;
+        }
+
+        self.histogram.observe(value);
+        set_exemplar();
+
+        if current.values.len() < MAX_LATENCY_SAMPLES_PER_SECOND {
+            current.values.push(value);
+        }
+
+        true
+    }
+
+    fn is_evicted(&self) -> bool {
+        self.current.lock().unwrap().evicted
+    }
+
+    /// Moves the observations of the last second to the window and returns the sorted window values.
+    /// `None` means the label set was idle for `ttl_ticks` and is evicted.
+    fn tick(&self, ttl_ticks: Option<u64>) -> Option<Vec<f64>> {
+        let mut state = self.state.lock().unwrap();
+
+        {
+            let mut current = self.current.lock().unwrap();
+            let values = std::mem::take(&mut current.values);
+
+            if values.is_empty() {
+                state.idle_ticks += 1;
+            } else {
+                state.idle_ticks = 0;
+            }
+
+            if ttl_ticks.is_some_and(|ttl_ticks| state.idle_ticks >= ttl_ticks) {
+                current.evicted = true;
+                return None;
+            }
+
+            state.window.push_back(values);
+        }
+
+        while state.window.len() > LATENCY_PERCENTILES_WINDOW {
+            state.window.pop_front();
+        }
+
+        let mut values: Vec<f64> = state.window.iter().flatten().copied().collect();
+        values.sort_by(|a, b| a.total_cmp(b));
+        Some(values)
+    }
+}
+
+struct LatencyMetrics {
+    histogram_name: String,
+    label_names: Vec<String>,
+    const_labels: Vec<(String, String)>,
+    histogram: prometheus::HistogramVec,
+    percentiles: Vec<prometheus::GaugeVec>,
+}
+
+impl LatencyMetrics {
+    fn remove_series(&self, labels: &LabelsKey) {
+        let label_values: Vec<&str> = labels.iter().map(|(_, value)| value.as_str()).collect();
+
+        let _ = self.histogram.remove_label_values(&label_values);
+        for gauge in self.percentiles.iter() {
+            let _ = gauge.remove_label_values(&label_values);
+        }
+
+        remove_exemplars(&self.histogram_name, &self.get_series_labels(labels));
+    }
+
+    fn get_series_labels(&self, labels: &LabelsKey) -> Vec<(String, String)> {
+        let mut result = self.const_labels.clone();
+        result.extend(labels.iter().cloned());
+        result
+    }
+}
+
+/// Publishes `<name>` histogram in seconds and `<name>_p50`, `<name>_p90`, `<name>_p99` gauges.
+/// Metrics are registered with the tracker; observations with other label names are dropped.
+/// A label set without observations for `DEFAULT_LABEL_SET_TTL` is removed together with its series.
+pub struct LatencyTracker {
+    metric_name: String,
+    buckets: Vec<f64>,
+    label_set_ttl_ticks: u64,
+    samples: ArcSwap<HashMap<LabelsKey, Arc<LatencySamples>>>,
+    // Label sets are added and removed under the lock, so a new label set never gets a removed series.
+    samples_update_lock: Mutex<()>,
+    metrics: LatencyMetrics,
+    label_names_mismatch_logged: AtomicBool,
+}
+
+impl LatencyTracker {
+    pub(crate) fn new(
+        metric_name: impl Into<String>,
+        label_names: &[&str],
+        buckets: &[f64],
+    ) -> Self {
+        let metric_name = metric_name.into();
+        let buckets = get_buckets(buckets);
+
+        if buckets.is_empty() {
+            panic!("Latency tracker {} buckets can not be empty", metric_name);
+        }
+
+        let metrics = match register_metrics(&metric_name, &get_label_names(label_names), &buckets)
+        {
+            Ok(metrics) => metrics,
+            Err(err) => panic!(
+                "Can not register metrics of latency tracker {}. Err: {}",
+                metric_name, err
+            ),
+        };
+
+        Self {
+            metric_name,
+            buckets,
+            label_set_ttl_ticks: DEFAULT_LABEL_SET_TTL.as_secs(),
+            samples: ArcSwap::from_pointee(HashMap::new()),
+            samples_update_lock: Mutex::new(()),
+            metrics,
+            label_names_mismatch_logged: AtomicBool::new(false),
+        }
+    }
+
+    pub fn get_metric_name(&self) -> &str {
+        self.metric_name.as_str()
+    }
+
+    pub(crate) fn has_same_metrics(&self, label_names: &[&str], buckets: &[f64]) -> bool {
+        self.metrics.label_names == get_label_names(label_names)
+            && self.buckets == get_buckets(buckets)
+    }
+
+    pub fn observe(&self, duration: Duration, labels: &[(&str, &str)]) {
+        self.observe_with_key(normalize_labels(labels), duration, None);
+    }
+
+    /// The observation becomes the exemplar of its histogram bucket in OpenMetrics output.
+    pub fn observe_with_exemplar(
+        &self,
+        duration: Duration,
+        labels: &[(&str, &str)],
+        trace_id: &str,
+    ) {
+        self.observe_with_key(normalize_labels(labels), duration, Some(trace_id));
+    }
+
+    fn observe_with_key(&self, key: LabelsKey, duration: Duration, trace_id: Option<&str>) {
+        let value = duration.as_secs_f64();
+
+        loop {
+            let samples = match self.samples.load().get(&key) {
+                Some(samples) if !samples.is_evicted() => samples.clone(),
+                _ => match self.try_insert(&key) {
+                    Some(samples) => samples,
+                    None => return,
+                },
+            };
+
+            let set_exemplar = || {
+                if let Some(trace_id) = trace_id {
+                    self.set_exemplar(&key, value, trace_id);
+                }
+            };
+
+            if samples.observe(value, set_exemplar) {
+                break;
+            }
+        }
+    }
+
+    fn set_exemplar(&self, key: &LabelsKey, value: f64, trace_id: &str) {
+        super::set_exemplar(
+            &self.metrics.histogram_name,
+            &self.metrics.get_series_labels(key),
+            get_upper_bound(&self.buckets, value),
+            MetricsExemplar::new(trace_id, value),
+        );
+    }
+
+    /// The time until the guard is dropped is observed.
+    pub fn start_timer(&self, labels: &[(&str, &str)]) -> LatencyTimer<'_> {
+        LatencyTimer {
+            tracker: self,
+            labels: normalize_labels(labels),
+            trace_id: None,
+            started: Instant::now(),
+        }
+    }
+
+    fn try_insert(&self, key: &LabelsKey) -> Option<Arc<LatencySamples>> {
+        let metrics = self.get_metrics(key)?;
+        let _update_lock = self.samples_update_lock.lock().unwrap();
+
+        let label_values: Vec<&str> = key.iter().map(|(_, value)| value.as_str()).collect();
+        let histogram = metrics
+            .histogram
+            .get_metric_with_label_values(&label_values)
+            .ok()?;
+
+        let new_samples = Arc::new(LatencySamples::new(histogram));
+
+        self.samples.rcu(|prev| {
+            if prev.get(key).is_some_and(|samples| !samples.is_evicted()) {
+                return prev.clone();
+            }
+            let mut new = (**prev).clone();
+            new.insert(key.clone(), new_samples.clone());
+            Arc::new(new)
+        });
+
+        self.samples.load().get(key).cloned()
+    }
+
+    fn get_metrics(&self, labels: &LabelsKey) -> Option<&LatencyMetrics> {
+        let metrics = &self.metrics;
+
+        let label_names_match = metrics.label_names.len() == labels.len()
+            && metrics
+                .label_names
+                .iter()
+                .zip(labels.iter())
+                .all(|(registered, (name, _))| registered == name);
+
+        if label_names_match {
+            return Some(metrics);
+        }
+
+        metrics::counter!(
+            "latency_tracker_dropped_observations",
+            &[("metric", self.metric_name.clone())]
+        )
+        .increment(1);
+
+        if !self
+            .label_names_mismatch_logged
+            .swap(true, Ordering::Relaxed)
+        {
+            let label_names: Vec<&str> = labels.iter().map(|(name, _)| name.as_str()).collect();
+            my_logger::LOGGER.write_error(
+                "LatencyTracker".to_string(),
+                format!(
+                    "Observation of {} with labels {:?} is dropped. Tracker labels are {:?}",
+                    self.metric_name, label_names, metrics.label_names
+                ),
+                my_logger::LogEventCtx::new(),
+            );
+        }
+
+        None
+    }
+
+    fn tick(&self) {
+        let metrics = &self.metrics;
+        let mut evicted = vec![];
+
+        for (labels, samples) in self.samples.load().iter() {
+            // The set without labels is never removed.
+            let ttl_ticks = if labels.is_empty() {
+                None
+            } else {
+                Some(self.label_set_ttl_ticks)
+            };
+
+            let Some(values) = samples.tick(ttl_ticks) else {
+                evicted.push((labels.clone(), samples.clone()));
+                continue;
+            };
+
+            let label_values: Vec<&str> = labels.iter().map(|(_, value)| value.as_str()).collect();
+
+            for ((_, percentile), gauge) in PERCENTILES.iter().zip(metrics.percentiles.iter()) {
+                if let Ok(gauge) = gauge.get_metric_with_label_values(&label_values) {
+                    gauge.set(get_percentile(&values, *percentile));
+                }
+            }
+        }
+
+        if evicted.is_empty() {
+            return;
+        }
+
+        let _update_lock = self.samples_update_lock.lock().unwrap();
+
+        self.samples.rcu(|prev| {
+            let mut new = (**prev).clone();
+            for (labels, samples) in evicted.iter() {
+                if new.get(labels).is_some_and(|itm| Arc::ptr_eq(itm, samples)) {
+                    new.remove(labels);
+                }
+            }
+            Arc::new(new)
+        });
+
+        let samples = self.samples.load();
+        for (labels, _) in evicted.iter() {
+            // An observation could create the label set again before the lock was taken.
+            if !samples.contains_key(labels) {
+                metrics.remove_series(labels);
+            }
+        }
+    }
+}
+
+// Nearest-rank percentile of sorted values.
+fn get_percentile(values: &[f64], percentile: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+
+    let rank = (percentile * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+// Sorted like the label names of `normalize_labels`.
+fn get_label_names(label_names: &[&str]) -> Vec<String> {
+    let mut result: Vec<String> = label_names.iter().map(|name| name.to_string()).collect();
+    result.sort();
+    result
+}
+
+fn get_buckets(buckets: &[f64]) -> Vec<f64> {
+    let mut result = buckets.to_vec();
+    result.sort_by(|a, b| a.total_cmp(b));
+    result.dedup();
+    result
+}
+
+fn register_metrics(
+    metric_name: &str,
+    label_names: &[String],
+    buckets: &[f64],
+) -> Result<LatencyMetrics, String> {
+    let label_names: Vec<&str> = label_names.iter().map(|name| name.as_str()).collect();
+    let registry = prometheus::default_registry();
+
+    let opts = super::get_prometheus_opts(metric_name, &label_names);
+    let histogram_name = opts.name.clone();
+    let const_labels = opts
+        .const_labels
+        .iter()
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+
+    let histogram = prometheus::HistogramVec::new(
+        prometheus::HistogramOpts::from(opts).buckets(buckets.to_vec()),
+        &label_names,
+    )
+    .map_err(|err| err.to_string())?;
+    registry
+        .register(Box::new(histogram.clone()))
+        .map_err(|err| err.to_string())?;
+
+    let mut percentiles = Vec::with_capacity(PERCENTILES.len());
+    for (suffix, _) in PERCENTILES.iter() {
+        let metric_name = format!("{}_{}", metric_name, suffix);
+        let opts = super::get_prometheus_opts(metric_name.as_str(), &label_names);
+        let gauge = prometheus::GaugeVec::new(opts, &label_names).map_err(|err| err.to_string())?;
+        registry
+            .register(Box::new(gauge.clone()))
+            .map_err(|err| err.to_string())?;
+        percentiles.push(gauge);
+    }
+
+    Ok(LatencyMetrics {
+        histogram_name,
+        label_names: label_names.iter().map(|name| name.to_string()).collect(),
+        const_labels,
+        histogram,
+        percentiles,
+    })
+}
+
+pub struct LatencyTimer<'s> {
+    tracker: &'s LatencyTracker,
+    labels: LabelsKey,
+    trace_id: Option<String>,
+    started: Instant,
+}
+
+impl LatencyTimer<'_> {
+    /// See `LatencyTracker::observe_with_exemplar`.
+    pub fn with_exemplar(mut self, trace_id: impl Into<String>) -> Self {
+        self.trace_id = Some(trace_id.into());
+        self
+    }
+}
+
+impl Drop for LatencyTimer<'_> {
+    fn drop(&mut self) {
+        self.tracker.observe_with_key(
+            std::mem::take(&mut self.labels),
+            self.started.elapsed(),
+            self.trace_id.as_deref(),
+        );
+    }
+}
+
+pub(crate) struct LatencyTrackersTimerTick {
+    pub trackers: Arc<ArcSwap<Vec<Arc<LatencyTracker>>>>,
+}
+
+#[async_trait]
+impl MyTimerTick for LatencyTrackersTimerTick {
+    async fn tick(&self) {
+        let trackers = self.trackers.load_full();
+        for tracker in trackers.iter() {
+            tracker.tick();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::get_exemplar;
+    use super::*;
+
+    fn get_samples() -> LatencySamples {
+        let opts = prometheus::HistogramOpts::new("test_latency_sec", "test_latency_sec");
+        LatencySamples::new(prometheus::Histogram::with_opts(opts).unwrap())
+    }
+
+    #[test]
+    fn test_get_percentile() {
+        let values: Vec<f64> = (1..=100).map(|itm| itm as f64).collect();
+
+        assert_eq!(get_percentile(&values, 0.5), 50.0);
+        assert_eq!(get_percentile(&values, 0.9), 90.0);
+        assert_eq!(get_percentile(&values, 0.99), 99.0);
+        assert_eq!(get_percentile(&values, 1.0), 100.0);
+        assert_eq!(get_percentile(&values, 0.0), 1.0);
+    }
+
+    #[test]
+    fn test_get_percentile_of_few_values() {
+        assert_eq!(get_percentile(&[], 0.5), 0.0);
+        assert_eq!(get_percentile(&[0.2], 0.99), 0.2);
+        assert_eq!(get_percentile(&[0.1, 0.2, 0.3], 0.5), 0.2);
+        assert_eq!(get_percentile(&[0.1, 0.2, 0.3], 0.9), 0.3);
+    }
+
+    #[test]
+    fn test_window_keeps_last_seconds_sorted() {
+        let samples = get_samples();
+
+        samples.observe(0.3, || {});
+        samples.observe(0.1, || {});
+        assert_eq!(samples.tick(None), Some(vec![0.1, 0.3]));
+
+        samples.observe(0.2, || {});
+        assert_eq!(samples.tick(None), Some(vec![0.1, 0.2, 0.3]));
+
+        for _ in 0..LATENCY_PERCENTILES_WINDOW - 2 {
+            samples.tick(None);
+        }
+        assert_eq!(samples.tick(None), Some(vec![0.2]));
+        assert_eq!(samples.tick(None), Some(vec![]));
+        assert_eq!(samples.histogram.get_sample_count(), 3);
+    }
+
+    #[test]
+    fn test_samples_per_second_are_limited() {
+        let samples = get_samples();
+
+        for _ in 0..MAX_LATENCY_SAMPLES_PER_SECOND + 5 {
+            samples.observe(0.1, || {});
+        }
+
+        let values = samples.tick(None).unwrap();
+        assert_eq!(values.len(), MAX_LATENCY_SAMPLES_PER_SECOND);
+        assert_eq!(
+            samples.histogram.get_sample_count(),
+            MAX_LATENCY_SAMPLES_PER_SECOND as u64 + 5
+        );
+    }
+
+    #[test]
+    fn test_idle_label_set_is_evicted() {
+        let samples = get_samples();
+
+        samples.observe(0.1, || {});
+        assert!(samples.tick(Some(2)).is_some());
+        assert!(samples.tick(Some(2)).is_some());
+        assert!(samples.tick(Some(2)).is_none());
+
+        assert!(samples.is_evicted());
+        assert!(!samples.observe(0.1, || panic!("Exemplar of an evicted label set is set")));
+        assert_eq!(samples.histogram.get_sample_count(), 1);
+    }
+
+    #[test]
+    fn test_observations_with_other_label_names_are_dropped() {
+        let tracker = LatencyTracker::new(
+            "test_latency_tracker_label_names_sec",
+            &["method"],
+            &[0.1, 1.0],
+        );
+
+        tracker.observe(Duration::from_millis(50), &[("method", "GET")]);
+        tracker.observe(Duration::from_millis(50), &[("region", "eu")]);
+        tracker.observe(Duration::from_millis(50), &[("method", "POST")]);
+
+        let samples = tracker.samples.load();
+        let mut keys: Vec<&LabelsKey> = samples.keys().collect();
+        keys.sort();
+
+        assert_eq!(
+            keys,
+            vec![
+                &vec![("method".to_string(), "GET".to_string())],
+                &vec![("method".to_string(), "POST".to_string())],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exemplars_are_removed_with_evicted_series() {
+        let mut tracker = LatencyTracker::new(
+            "test_latency_tracker_exemplars_sec",
+            &["query"],
+            &[0.1, 1.0],
+        );
+        tracker.label_set_ttl_ticks = 1;
+
+        let labels = [("query", "get_user")];
+        tracker.observe_with_exemplar(Duration::from_millis(50), &labels, "12345");
+
+        let key = normalize_labels(&labels);
+        let metrics = tracker.get_metrics(&key).unwrap();
+        let series_labels = metrics.get_series_labels(&key);
+
+        let exemplar = get_exemplar(&metrics.histogram_name, &series_labels, 0.1);
+        assert_eq!(exemplar.unwrap().trace_id, "12345");
+
+        tracker.tick();
+        tracker.tick();
+
+        assert!(tracker.samples.load().is_empty());
+        assert!(get_exemplar(&metrics.histogram_name, &series_labels, 0.1).is_none());
+    }
+
+    #[test]
+    fn test_metrics_are_registered_with_the_tracker() {
+        let tracker = LatencyTracker::new(
+            "test_latency_tracker_registered_sec",
+            &["region", "method"],
+            &[1.0, 0.1, 1.0],
+        );
+
+        assert!(tracker.has_same_metrics(&["method", "region"], &[0.1, 1.0]));
+        assert!(!tracker.has_same_metrics(&["method"], &[0.1, 1.0]));
+        assert!(!tracker.has_same_metrics(&["method", "region"], &[0.5]));
+
+        let families = prometheus::default_registry().gather();
+        assert!(families
+            .iter()
+            .any(|family| family.get_name() == "test_latency_tracker_registered_sec_p99"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Can not register metrics of latency tracker")]
+    fn test_tracker_with_registered_name_panics() {
+        let _tracker = LatencyTracker::new("test_latency_tracker_twice_sec", &[], &[0.1]);
+        let _ = LatencyTracker::new("test_latency_tracker_twice_sec", &[], &[0.1]);
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+/// Applied to every metric published through the `metrics` facade, including the SDK ones.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// `my_service` turns `http_request_count` into `my_service_http_request_count`.
+    pub prefix: Option<String>,
+    /// Added to every series, e.g. `app`, `version`, `env`.
+    pub constant_labels: BTreeMap<String, String>,
+    /// The first bucket set whose pattern matches the metric name (without prefix) wins.
+    pub histogram_buckets: Vec<HistogramBuckets>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HistogramBuckets {
+    /// Metric name. A leading or trailing `*` matches any prefix or suffix, e.g. `*_duration_sec`.
+    pub pattern: String,
+    pub buckets: Vec<f64>,
+}
+
+impl HistogramBuckets {
+    pub fn new(pattern: impl Into<String>, buckets: &[f64]) -> Self {
+        Self {
+            pattern: pattern.into(),
+            buckets: buckets.to_vec(),
+        }
+    }
+}
+
+impl MetricsConfig {
+    pub fn get_histogram_buckets(&self, metric_name: &str) -> Option<Vec<f64>> {
+        let histogram_buckets = self
+            .histogram_buckets
+            .iter()
+            .find(|itm| name_matches(itm.pattern.as_str(), metric_name))?;
+
+        let mut result = histogram_buckets.buckets.clone();
+        result.sort_by(|a, b| a.total_cmp(b));
+        result.dedup();
+
+        if result.is_empty() {
+            return None;
+        }
+
+        Some(result)
+    }
+
+    pub fn get_metric_name(&self, metric_name: &str) -> String {
+        match self.prefix.as_ref() {
+            Some(prefix) => format!("{}_{}", prefix, metric_name),
+            None => metric_name.to_string(),
+        }
+    }
+}
+
+fn name_matches(pattern: &str, metric_name: &str) -> bool {
+    if pattern == "*" {
+        return true;
+    }
+
+    if let Some(suffix) = pattern.strip_prefix('*') {
+        return metric_name.ends_with(suffix);
+    }
+
+    if let Some(prefix) = pattern.strip_suffix('*') {
+        return metric_name.starts_with(prefix);
+    }
+
+    pattern == metric_name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_matches() {
+        assert!(name_matches("*", "http_request_count"));
+        assert!(name_matches("*_duration_sec", "http_request_duration_sec"));
+        assert!(name_matches("http_*", "http_request_duration_sec"));
+        assert!(name_matches("http_request_count", "http_request_count"));
+
+        assert!(!name_matches(
+            "*_duration_sec",
+            "http_request_duration_sec_sum"
+        ));
+        assert!(!name_matches("grpc_*", "http_request_duration_sec"));
+        assert!(!name_matches("http_request", "http_request_count"));
+    }
+
+    #[test]
+    fn test_first_matching_buckets_win() {
+        let config = MetricsConfig {
+            histogram_buckets: vec![
+                HistogramBuckets::new("http_request_duration_sec", &[0.5, 0.1, 0.5]),
+                HistogramBuckets::new("*_duration_sec", &[1.0]),
+                HistogramBuckets::new("*", &[10.0]),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config.get_histogram_buckets("http_request_duration_sec"),
+            Some(vec![0.1, 0.5])
+        );
+        assert_eq!(
+            config.get_histogram_buckets("grpc_request_duration_sec"),
+            Some(vec![1.0])
+        );
+        assert_eq!(
+            config.get_histogram_buckets("http_response_size_bytes"),
+            Some(vec![10.0])
+        );
+    }
+
+    #[test]
+    fn test_empty_buckets_fall_back_to_default() {
+        let config = MetricsConfig {
+            histogram_buckets: vec![HistogramBuckets::new("*_duration_sec", &[])],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config.get_histogram_buckets("http_request_duration_sec"),
+            None
+        );
+        assert_eq!(MetricsConfig::default().get_histogram_buckets("any"), None);
+    }
+
+    #[test]
+    fn test_prefix() {
+        let config = MetricsConfig {
+            prefix: Some("my_service".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config.get_metric_name("http_request_count"),
+            "my_service_http_request_count"
+        );
+        assert_eq!(
+            MetricsConfig::default().get_metric_name("http_request_count"),
+            "http_request_count"
+        );
+    }
+}
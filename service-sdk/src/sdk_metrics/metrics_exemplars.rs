@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use my_telemetry::MyTelemetryContext;
+use rust_extensions::date_time::DateTimeAsMicroseconds;
+
+use super::get_metrics_config;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ExemplarKey {
+    metric_name: String,
+    labels: Vec<(String, String)>,
+    upper_bound: u64,
+}
+
+/// Last observation of a histogram bucket with the trace id it belongs to. Rendered only in OpenMetrics output.
+#[derive(Debug, Clone)]
+pub(crate) struct MetricsExemplar {
+    pub trace_id: String,
+    pub value: f64,
+    /// Unix time in seconds.
+    pub timestamp: f64,
+}
+
+impl MetricsExemplar {
+    pub fn new(trace_id: impl Into<String>, value: f64) -> Self {
+        Self {
+            trace_id: trace_id.into(),
+            value,
+            timestamp: DateTimeAsMicroseconds::now().unix_microseconds as f64 / 1_000_000.0,
+        }
+    }
+}
+
+/// Trace id of a request is the process id of its telemetry context.
+pub(crate) fn get_trace_id(ctx: &MyTelemetryContext) -> Option<String> {
+    match ctx {
+        MyTelemetryContext::Single(process_id) => Some(process_id.to_string()),
+        MyTelemetryContext::Multiple(process_ids) => process_ids.first().map(|itm| itm.to_string()),
+    }
+}
+
+pub(crate) fn get_upper_bound(buckets: &[f64], value: f64) -> f64 {
+    buckets
+        .iter()
+        .copied()
+        .find(|bucket| value <= *bucket)
+        .unwrap_or(f64::INFINITY)
+}
+
+static EXEMPLARS: OnceLock<Mutex<HashMap<ExemplarKey, MetricsExemplar>>> = OnceLock::new();
+
+fn get_exemplars() -> &'static Mutex<HashMap<ExemplarKey, MetricsExemplar>> {
+    EXEMPLARS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn get_key(metric_name: &str, labels: &[(String, String)], upper_bound: f64) -> ExemplarKey {
+    let mut labels = labels.to_vec();
+    labels.sort();
+
+    ExemplarKey {
+        metric_name: metric_name.to_string(),
+        labels,
+        upper_bound: upper_bound.to_bits(),
+    }
+}
+
+/// `labels` are all the labels of the series (constant ones included) except `le`.
+pub(crate) fn set_exemplar(
+    metric_name: &str,
+    labels: &[(String, String)],
+    upper_bound: f64,
+    exemplar: MetricsExemplar,
+) {
+    let key = get_key(metric_name, labels, upper_bound);
+    get_exemplars().lock().unwrap().insert(key, exemplar);
+}
+
+pub(crate) fn get_exemplar(
+    metric_name: &str,
+    labels: &[(String, String)],
+    upper_bound: f64,
+) -> Option<MetricsExemplar> {
+    let key = get_key(metric_name, labels, upper_bound);
+    get_exemplars().lock().unwrap().get(&key).cloned()
+}
+
+/// Exemplar of a histogram recorded through the `metrics` facade. The metric name and labels
+/// are completed with the prefix and constant labels the same way `SdkMetricsRecorder` does.
+pub(crate) fn set_histogram_exemplar(
+    metric_name: &str,
+    labels: &[(&'static str, String)],
+    value: f64,
+    trace_id: &str,
+) {
+    let config = get_metrics_config().load();
+
+    let buckets = config
+        .get_histogram_buckets(metric_name)
+        .unwrap_or_else(|| prometheus::DEFAULT_BUCKETS.to_vec());
+
+    let mut series_labels: Vec<(String, String)> = labels
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.clone()))
+        .collect();
+
+    for (name, value) in config.constant_labels.iter() {
+        if !labels
+            .iter()
+            .any(|(label_name, _)| *label_name == name.as_str())
+        {
+            series_labels.push((name.clone(), value.clone()));
+        }
+    }
+
+    set_exemplar(
+        &config.get_metric_name(metric_name),
+        &series_labels,
+        get_upper_bound(&buckets, value),
+        MetricsExemplar::new(trace_id, value),
+    );
+}
+
+/// Removes the exemplars of all buckets of a series, once the series itself is removed.
+pub(crate) fn remove_exemplars(metric_name: &str, labels: &[(String, String)]) {
+    let mut labels = labels.to_vec();
+    labels.sort();
+
+    get_exemplars()
+        .lock()
+        .unwrap()
+        .retain(|key, _| key.metric_name != metric_name || key.labels != labels);
+}
@@ -0,0 +1,416 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use serde::Serialize;
+
+use super::get_exemplar;
+
+pub(crate) const OPEN_METRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+pub(crate) const JSON_METRICS_CONTENT_TYPE: &str = "application/json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MetricsFormat {
+    Prometheus,
+    /// OpenMetrics text with exemplars of histogram buckets.
+    OpenMetrics,
+    Json,
+}
+
+impl MetricsFormat {
+    /// OpenMetrics is served only to scrapers which ask for it.
+    pub(crate) fn from_accept_header(accept: Option<&str>) -> Self {
+        match accept {
+            Some(accept) if accept.contains("application/openmetrics-text") => Self::OpenMetrics,
+            _ => Self::Prometheus,
+        }
+    }
+
+    pub(crate) fn get_content_type(&self) -> &'static str {
+        match self {
+            Self::Prometheus => prometheus::TEXT_FORMAT,
+            Self::OpenMetrics => OPEN_METRICS_CONTENT_TYPE,
+            Self::Json => JSON_METRICS_CONTENT_TYPE,
+        }
+    }
+
+    pub(crate) fn render(
+        &self,
+        families: &[prometheus::proto::MetricFamily],
+    ) -> Result<String, String> {
+        let report = prometheus::TextEncoder::new()
+            .encode_to_string(families)
+            .map_err(|err| err.to_string())?;
+
+        match self {
+            Self::Prometheus => Ok(report),
+            Self::OpenMetrics => Ok(to_open_metrics(&parse_text_report(&report))),
+            Self::Json => {
+                serde_json::to_string(&parse_text_report(&report)).map_err(|err| err.to_string())
+            }
+        }
+    }
+}
+
+/// `accept_encoding` is the value of the `Accept-Encoding` header.
+pub(crate) fn accepts_gzip(accept_encoding: Option<&str>) -> bool {
+    let Some(accept_encoding) = accept_encoding else {
+        return false;
+    };
+
+    accept_encoding.split(',').any(|encoding| {
+        let mut parts = encoding.split(';').map(|itm| itm.trim());
+
+        if parts.next() != Some("gzip") {
+            return false;
+        }
+
+        !parts.any(|param| matches!(param, "q=0" | "q=0.0" | "q=0.00" | "q=0.000"))
+    })
+}
+
+pub(crate) fn gzip(content: &[u8]) -> Result<Vec<u8>, String> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(content).map_err(|err| err.to_string())?;
+    encoder.finish().map_err(|err| err.to_string())
+}
+
+#[derive(Debug, Serialize)]
+struct MetricsFamily {
+    name: String,
+    #[serde(rename = "type")]
+    metric_type: String,
+    help: String,
+    samples: Vec<MetricsSample>,
+}
+
+#[derive(Debug, Serialize)]
+struct MetricsSample {
+    name: String,
+    labels: BTreeMap<String, String>,
+    value: f64,
+}
+
+// Reads the output of `prometheus::TextEncoder`: every family starts with `# HELP` and `# TYPE` lines.
+fn parse_text_report(report: &str) -> Vec<MetricsFamily> {
+    let mut result: Vec<MetricsFamily> = Vec::new();
+
+    for line in report.lines() {
+        if let Some(help) = line.strip_prefix("# HELP ") {
+            let (name, help) = help.split_once(' ').unwrap_or((help, ""));
+            result.push(MetricsFamily {
+                name: name.to_string(),
+                metric_type: "untyped".to_string(),
+                help: unescape(help),
+                samples: Vec::new(),
+            });
+            continue;
+        }
+
+        if let Some(metric_type) = line.strip_prefix("# TYPE ") {
+            let (name, metric_type) = metric_type.split_once(' ').unwrap_or((metric_type, ""));
+            if let Some(family) = result.last_mut().filter(|itm| itm.name == name) {
+                family.metric_type = metric_type.to_string();
+            }
+            continue;
+        }
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (Some(sample), Some(family)) = (parse_sample(line), result.last_mut()) else {
+            continue;
+        };
+
+        family.samples.push(sample);
+    }
+
+    result
+}
+
+fn parse_sample(line: &str) -> Option<MetricsSample> {
+    let name_end = line.find(['{', ' '])?;
+    let name = &line[..name_end];
+    let mut rest = &line[name_end..];
+
+    let mut labels = BTreeMap::new();
+
+    if let Some(labels_str) = rest.strip_prefix('{') {
+        let mut chars = labels_str.char_indices();
+        let mut label_name = String::new();
+        let mut labels_end = None;
+
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '}' => {
+                    labels_end = Some(index + 1);
+                    break;
+                }
+                ',' | ' ' => {}
+                '=' => {
+                    chars.next(); // opening quote
+                    let mut value = String::new();
+                    while let Some((_, c)) = chars.next() {
+                        match c {
+                            '"' => break,
+                            '\\' => match chars.next().map(|(_, c)| c) {
+                                Some('n') => value.push('\n'),
+                                Some(c) => value.push(c),
+                                None => {}
+                            },
+                            c => value.push(c),
+                        }
+                    }
+                    labels.insert(std::mem::take(&mut label_name), value);
+                }
+                c => label_name.push(c),
+            }
+        }
+
+        rest = &labels_str[labels_end?..];
+    }
+
+    let value = rest.split_whitespace().next()?;
+
+    Some(MetricsSample {
+        name: name.to_string(),
+        labels,
+        value: parse_value(value)?,
+    })
+}
+
+fn parse_value(value: &str) -> Option<f64> {
+    match value {
+        "+Inf" => Some(f64::INFINITY),
+        "-Inf" => Some(f64::NEG_INFINITY),
+        "NaN" => Some(f64::NAN),
+        _ => value.parse().ok(),
+    }
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('"', "\\\"")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        return "NaN".to_string();
+    }
+
+    if value.is_infinite() {
+        return if value > 0.0 { "+Inf" } else { "-Inf" }.to_string();
+    }
+
+    value.to_string()
+}
+
+fn format_labels(labels: &BTreeMap<String, String>) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+        .collect();
+
+    format!("{{{}}}", labels.join(","))
+}
+
+fn to_open_metrics(families: &[MetricsFamily]) -> String {
+    let mut result = String::new();
+
+    for family in families {
+        let metric_type = match family.metric_type.as_str() {
+            "untyped" => "unknown",
+            metric_type => metric_type,
+        };
+
+        // OpenMetrics counters are named without `_total`, which their samples must have.
+        let family_name = match metric_type {
+            "counter" => family
+                .name
+                .strip_suffix("_total")
+                .unwrap_or(family.name.as_str()),
+            _ => family.name.as_str(),
+        };
+
+        result.push_str(&format!("# TYPE {} {}\n", family_name, metric_type));
+        result.push_str(&format!(
+            "# HELP {} {}\n",
+            family_name,
+            escape(&family.help)
+        ));
+
+        for sample in family.samples.iter() {
+            let sample_name = match metric_type {
+                "counter" => format!("{}_total", family_name),
+                _ => sample.name.clone(),
+            };
+
+            result.push_str(&sample_name);
+            result.push_str(&format_labels(&sample.labels));
+            result.push(' ');
+            result.push_str(&format_value(sample.value));
+
+            if metric_type == "histogram" {
+                if let Some(exemplar) = get_bucket_exemplar(family, sample) {
+                    result.push_str(&exemplar);
+                }
+            }
+
+            result.push('\n');
+        }
+    }
+
+    result.push_str("# EOF\n");
+    result
+}
+
+fn get_bucket_exemplar(family: &MetricsFamily, sample: &MetricsSample) -> Option<String> {
+    if sample.name.strip_suffix("_bucket")? != family.name {
+        return None;
+    }
+
+    let upper_bound = parse_value(sample.labels.get("le")?)?;
+
+    let labels: Vec<(String, String)> = sample
+        .labels
+        .iter()
+        .filter(|(name, _)| name.as_str() != "le")
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+
+    let exemplar = get_exemplar(&family.name, &labels, upper_bound)?;
+
+    Some(format!(
+        " # {{trace_id=\"{}\"}} {} {}",
+        escape(&exemplar.trace_id),
+        format_value(exemplar.value),
+        exemplar.timestamp
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    fn get_registry() -> prometheus::Registry {
+        let registry = prometheus::Registry::new();
+
+        let counter = prometheus::IntCounterVec::new(
+            prometheus::Opts::new("http_request_count", "Count of HTTP requests"),
+            &["method"],
+        )
+        .unwrap();
+        counter.with_label_values(&["GET"]).inc_by(3);
+        registry.register(Box::new(counter)).unwrap();
+
+        let gauge =
+            prometheus::Gauge::new("http_requests_in_flight", "Requests in flight").unwrap();
+        gauge.set(2.0);
+        registry.register(Box::new(gauge)).unwrap();
+
+        registry
+    }
+
+    #[test]
+    fn test_open_metrics_is_served_only_when_accepted() {
+        assert_eq!(
+            MetricsFormat::from_accept_header(Some(
+                "application/openmetrics-text;version=1.0.0,text/plain;version=0.0.4;q=0.5"
+            )),
+            MetricsFormat::OpenMetrics
+        );
+        assert_eq!(
+            MetricsFormat::from_accept_header(Some("text/plain")),
+            MetricsFormat::Prometheus
+        );
+        assert_eq!(
+            MetricsFormat::from_accept_header(None),
+            MetricsFormat::Prometheus
+        );
+    }
+
+    #[test]
+    fn test_open_metrics_counters_have_total_suffix() {
+        let report = MetricsFormat::OpenMetrics
+            .render(&get_registry().gather())
+            .unwrap();
+
+        assert_eq!(
+            report,
+            [
+                "# TYPE http_request_count counter",
+                "# HELP http_request_count Count of HTTP requests",
+                r#"http_request_count_total{method="GET"} 3"#,
+                "# TYPE http_requests_in_flight gauge",
+                "# HELP http_requests_in_flight Requests in flight",
+                "http_requests_in_flight 2",
+                "# EOF",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_json_lists_families_with_samples() {
+        let report = MetricsFormat::Json
+            .render(&get_registry().gather())
+            .unwrap();
+
+        assert_eq!(
+            report,
+            concat!(
+                r#"[{"name":"http_request_count","type":"counter","help":"Count of HTTP requests","samples":[{"name":"http_request_count","labels":{"method":"GET"},"value":3.0}]},"#,
+                r#"{"name":"http_requests_in_flight","type":"gauge","help":"Requests in flight","samples":[{"name":"http_requests_in_flight","labels":{},"value":2.0}]}]"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_gzip_is_used_only_when_accepted() {
+        assert!(accepts_gzip(Some("gzip, deflate")));
+        assert!(accepts_gzip(Some("deflate;q=1.0, gzip;q=0.5")));
+        assert!(!accepts_gzip(Some("gzip;q=0")));
+        assert!(!accepts_gzip(Some("identity")));
+        assert!(!accepts_gzip(None));
+
+        let report = MetricsFormat::Prometheus
+            .render(&get_registry().gather())
+            .unwrap();
+
+        let mut decoder = flate2::read::GzDecoder::new(gzip(report.as_bytes()).unwrap().as_slice());
+        let mut decompressed = String::new();
+        decoder.read_to_string(&mut decompressed).unwrap();
+
+        assert_eq!(decompressed, report);
+    }
+}
@@ -1,4 +1,6 @@
 #[cfg(feature = "grpc")]
+mod grpc_metrics_body;
+#[cfg(feature = "grpc")]
 mod grpc_metrics_middleware;
 mod events_per_second;
 mod http_metrics_middleware;
@@ -7,5 +9,27 @@ mod http_metrics_middleware;
 pub use grpc_metrics_middleware::*;
 pub use events_per_second::*;
 pub use http_metrics_middleware::*;
+mod http_in_flight_middleware;
+pub use http_in_flight_middleware::*;
+mod http_metrics_routes;
+pub use http_metrics_routes::*;
+mod http_request_metrics;
+pub use http_request_metrics::*;
 mod http_metrics_tech_middleware;
 pub use http_metrics_tech_middleware::*;
+mod metrics_config;
+pub use metrics_config::*;
+mod sdk_metrics_recorder;
+pub use sdk_metrics_recorder::*;
+mod process_metrics;
+pub use process_metrics::*;
+#[cfg(feature = "tokio-runtime-metrics")]
+mod tokio_runtime_metrics;
+#[cfg(feature = "tokio-runtime-metrics")]
+pub use tokio_runtime_metrics::*;
+mod latency_tracker;
+pub use latency_tracker::*;
+mod metrics_exemplars;
+pub use metrics_exemplars::*;
+mod metrics_formats;
+pub use metrics_formats::*;
@@ -0,0 +1,254 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rust_extensions::MyTimerTick;
+
+pub const PROCESS_METRICS_INTERVAL: Duration = Duration::from_secs(5);
+
+pub(crate) struct ProcessMetricsTimerTick {
+    pub app_name: &'static str,
+    pub app_version: &'static str,
+}
+
+#[async_trait]
+impl MyTimerTick for ProcessMetricsTimerTick {
+    async fn tick(&self) {
+        // Published on every tick, so the series follows MetricsConfig set after the start.
+        let labels = &[
+            ("app", self.app_name.to_string()),
+            ("version", self.app_version.to_string()),
+            ("git_sha", get_git_sha()),
+            ("rustc", env!("SERVICE_SDK_RUSTC_VERSION").to_string()),
+        ];
+        metrics::gauge!("service_build_info", labels).set(1.0);
+
+        #[cfg(target_os = "linux")]
+        linux::publish_process_metrics();
+    }
+}
+
+/// `GIT_SHA` env variable of the running process, or of the build if it is not set.
+fn get_git_sha() -> String {
+    if let Ok(git_sha) = std::env::var("GIT_SHA") {
+        return git_sha;
+    }
+
+    option_env!("SERVICE_SDK_GIT_SHA")
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::sync::OnceLock;
+
+    // Registered in prometheus directly: `metrics` counters are integers, CPU time is not.
+    static CPU_SECONDS_COUNTER: OnceLock<Option<prometheus::Counter>> = OnceLock::new();
+
+    pub fn publish_process_metrics() {
+        if let Ok(status) = std::fs::read_to_string("/proc/self/status") {
+            if let Some(value) = get_status_value(status.as_str(), "VmRSS:") {
+                metrics::gauge!("process_resident_memory_bytes").set((value * 1024) as f64);
+            }
+
+            if let Some(value) = get_status_value(status.as_str(), "VmSize:") {
+                metrics::gauge!("process_virtual_memory_bytes").set((value * 1024) as f64);
+            }
+
+            if let Some(value) = get_status_value(status.as_str(), "Threads:") {
+                metrics::gauge!("process_threads").set(value as f64);
+            }
+        }
+
+        if let Ok(stat) = std::fs::read_to_string("/proc/self/stat") {
+            let clock_ticks_per_sec = get_clock_ticks_per_sec();
+            let process_stat = parse_process_stat(stat.as_str());
+
+            if let Some(cpu_ticks) = process_stat.cpu_ticks {
+                set_cpu_seconds(cpu_ticks as f64 / clock_ticks_per_sec);
+            }
+
+            let boot_time = std::fs::read_to_string("/proc/stat")
+                .ok()
+                .and_then(|stat| parse_boot_time(stat.as_str()));
+
+            if let (Some(start_time), Some(boot_time)) = (process_stat.start_time, boot_time) {
+                metrics::gauge!("process_start_time_seconds")
+                    .set(boot_time as f64 + start_time as f64 / clock_ticks_per_sec);
+            }
+        }
+
+        if let Ok(fds) = std::fs::read_dir("/proc/self/fd") {
+            metrics::gauge!("process_open_fds").set(fds.count() as f64);
+        }
+
+        let max_fds = std::fs::read_to_string("/proc/self/limits")
+            .ok()
+            .and_then(|limits| parse_max_fds(limits.as_str()));
+
+        if let Some(max_fds) = max_fds {
+            metrics::gauge!("process_max_fds").set(max_fds as f64);
+        }
+    }
+
+    // CPU times in /proc are in USER_HZ units.
+    fn get_clock_ticks_per_sec() -> f64 {
+        let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+        if ticks > 0 {
+            ticks as f64
+        } else {
+            100.0
+        }
+    }
+
+    // The counter follows the CPU time of the process, which only grows.
+    fn set_cpu_seconds(value: f64) {
+        let Some(counter) = CPU_SECONDS_COUNTER.get_or_init(register_cpu_seconds_counter) else {
+            return;
+        };
+
+        let delta = value - counter.get();
+        if delta > 0.0 {
+            counter.inc_by(delta);
+        }
+    }
+
+    fn register_cpu_seconds_counter() -> Option<prometheus::Counter> {
+        let result = prometheus::Counter::with_opts(crate::get_prometheus_opts(
+            "process_cpu_seconds_total",
+            &[],
+        ))
+        .and_then(|counter| {
+            prometheus::default_registry().register(Box::new(counter.clone()))?;
+            Ok(counter)
+        });
+
+        match result {
+            Ok(counter) => Some(counter),
+            Err(err) => {
+                my_logger::LOGGER.write_error(
+                    "ProcessMetrics".to_string(),
+                    format!("Can not register process_cpu_seconds_total. Err: {}", err),
+                    my_logger::LogEventCtx::new(),
+                );
+                None
+            }
+        }
+    }
+
+    #[derive(Debug, Default, PartialEq, Eq)]
+    struct ProcessStat {
+        /// User and system time.
+        cpu_ticks: Option<u64>,
+        /// Since boot.
+        start_time: Option<u64>,
+    }
+
+    fn parse_process_stat(stat: &str) -> ProcessStat {
+        // The command name may contain spaces, so fields are counted after the closing bracket.
+        // The first one there is the 3rd field of the file.
+        let fields: Vec<&str> = match stat.rfind(')') {
+            Some(index) => stat[index + 1..].split_whitespace().collect(),
+            None => return ProcessStat::default(),
+        };
+
+        let get_field = |index: usize| fields.get(index).and_then(|itm| itm.parse::<u64>().ok());
+
+        ProcessStat {
+            cpu_ticks: get_field(11)
+                .zip(get_field(12))
+                .map(|(utime, stime)| utime + stime),
+            start_time: get_field(19),
+        }
+    }
+
+    fn get_status_value(status: &str, name: &str) -> Option<u64> {
+        let line = status.lines().find(|line| line.starts_with(name))?;
+        line[name.len()..].split_whitespace().next()?.parse().ok()
+    }
+
+    fn parse_boot_time(stat: &str) -> Option<u64> {
+        let line = stat.lines().find(|line| line.starts_with("btime "))?;
+        line["btime ".len()..].trim().parse().ok()
+    }
+
+    fn parse_max_fds(limits: &str) -> Option<u64> {
+        let line = limits
+            .lines()
+            .find(|line| line.starts_with("Max open files"))?;
+        line["Max open files".len()..]
+            .split_whitespace()
+            .next()?
+            .parse()
+            .ok()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const PROCESS_STAT: &str = "4242 (my service) S 1 4242 4242 0 -1 4194560 1520 0 0 0 \
+            250 130 0 0 20 0 12 0 98765 104857600 2048 18446744073709551615 1 1 0 0 0 0 0 0 0 0 0 0 17 3 0 0 0 0 0";
+
+        #[test]
+        fn test_process_stat() {
+            assert_eq!(
+                parse_process_stat(PROCESS_STAT),
+                ProcessStat {
+                    cpu_ticks: Some(380),
+                    start_time: Some(98765),
+                }
+            );
+        }
+
+        #[test]
+        fn test_process_stat_with_brackets_in_command() {
+            let stat = PROCESS_STAT.replace("(my service)", "(my (service) 2)");
+
+            assert_eq!(parse_process_stat(&stat).cpu_ticks, Some(380));
+        }
+
+        #[test]
+        fn test_broken_process_stat() {
+            assert_eq!(parse_process_stat(""), ProcessStat::default());
+            assert_eq!(
+                parse_process_stat("4242 (my service) S 1"),
+                ProcessStat::default()
+            );
+        }
+
+        #[test]
+        fn test_status_values() {
+            let status =
+                "Name:\tmy-service\nVmSize:\t  102400 kB\nVmRSS:\t    2048 kB\nThreads:\t12\n";
+
+            assert_eq!(get_status_value(status, "VmSize:"), Some(102400));
+            assert_eq!(get_status_value(status, "VmRSS:"), Some(2048));
+            assert_eq!(get_status_value(status, "Threads:"), Some(12));
+            assert_eq!(get_status_value(status, "VmSwap:"), None);
+        }
+
+        #[test]
+        fn test_boot_time() {
+            let stat = "cpu  1 2 3 4\nintr 100\nctxt 200\nbtime 1700000000\nprocesses 300\n";
+
+            assert_eq!(parse_boot_time(stat), Some(1700000000));
+            assert_eq!(parse_boot_time("cpu  1 2 3 4\n"), None);
+        }
+
+        #[test]
+        fn test_max_fds() {
+            let limits = concat!(
+                "Limit                     Soft Limit           Hard Limit           Units     \n",
+                "Max processes             63704                63704                processes \n",
+                "Max open files            1024                 524288               files     \n",
+            );
+
+            assert_eq!(parse_max_fds(limits), Some(1024));
+
+            let unlimited =
+                "Max open files            unlimited            unlimited            files     \n";
+            assert_eq!(parse_max_fds(unlimited), None);
+        }
+    }
+}
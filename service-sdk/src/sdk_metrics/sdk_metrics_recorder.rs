@@ -0,0 +1,269 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use arc_swap::ArcSwap;
+use metrics::{
+    Counter, Gauge, Histogram, Key, KeyName, Label, Metadata, Recorder, SharedString, Unit,
+};
+
+use super::MetricsConfig;
+
+static METRICS_CONFIG: OnceLock<ArcSwap<MetricsConfig>> = OnceLock::new();
+
+pub(crate) fn get_metrics_config() -> &'static ArcSwap<MetricsConfig> {
+    METRICS_CONFIG.get_or_init(|| ArcSwap::from_pointee(MetricsConfig::default()))
+}
+
+pub(crate) fn set_metrics_config(config: MetricsConfig) {
+    get_metrics_config().store(Arc::new(config));
+}
+
+/// Options of a metric registered in prometheus directly, bypassing the `metrics` facade.
+pub(crate) fn get_prometheus_opts(metric_name: &str, label_names: &[&str]) -> prometheus::Opts {
+    let config = get_metrics_config().load();
+
+    let const_labels: HashMap<String, String> = config
+        .constant_labels
+        .iter()
+        .filter(|(name, _)| !label_names.contains(&name.as_str()))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+
+    prometheus::Opts::new(config.get_metric_name(metric_name), metric_name)
+        .const_labels(const_labels)
+}
+
+/// Wraps `metrics_prometheus` recorder to apply `MetricsConfig` to every registered metric.
+pub(crate) struct SdkMetricsRecorder {
+    inner: metrics_prometheus::Recorder,
+    config: &'static ArcSwap<MetricsConfig>,
+    histograms_with_buckets: Mutex<HashSet<String>>,
+}
+
+impl SdkMetricsRecorder {
+    /// Records into the prometheus default registry with the config set by `set_metrics_config`.
+    pub fn install() {
+        let recorder = Self {
+            inner: metrics_prometheus::Recorder::builder().build(),
+            config: get_metrics_config(),
+            histograms_with_buckets: Mutex::new(HashSet::new()),
+        };
+
+        if metrics::set_global_recorder(recorder).is_err() {
+            my_logger::LOGGER.write_warning(
+                "SdkMetricsRecorder".to_string(),
+                "Metrics recorder is already installed".to_string(),
+                my_logger::LogEventCtx::new(),
+            );
+        }
+    }
+
+    #[cfg(test)]
+    pub fn new(registry: &prometheus::Registry, config: &'static ArcSwap<MetricsConfig>) -> Self {
+        Self {
+            inner: metrics_prometheus::Recorder::builder()
+                .with_registry(registry.clone())
+                .build(),
+            config,
+            histograms_with_buckets: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn get_key(&self, config: &MetricsConfig, key: &Key) -> Key {
+        if config.prefix.is_none() && config.constant_labels.is_empty() {
+            return key.clone();
+        }
+
+        let mut labels: Vec<Label> = key.labels().cloned().collect();
+        for (name, value) in config.constant_labels.iter() {
+            if !labels.iter().any(|itm| itm.key() == name.as_str()) {
+                labels.push(Label::new(name.clone(), value.clone()));
+            }
+        }
+
+        Key::from_parts(config.get_metric_name(key.name()), labels)
+    }
+
+    fn get_key_name(&self, key: KeyName) -> KeyName {
+        let config = self.config.load();
+        match config.prefix {
+            Some(_) => KeyName::from(config.get_metric_name(key.as_str())),
+            None => key,
+        }
+    }
+
+    // Histograms with custom buckets have to be registered in prometheus before the first use.
+    fn register_buckets(&self, config: &MetricsConfig, metric_name: &str, key: &Key) {
+        let Some(buckets) = config.get_histogram_buckets(metric_name) else {
+            return;
+        };
+
+        let mut histograms_with_buckets = self.histograms_with_buckets.lock().unwrap();
+        if histograms_with_buckets.contains(key.name()) {
+            return;
+        }
+        histograms_with_buckets.insert(key.name().to_string());
+
+        let label_names: Vec<&str> = key.labels().map(|itm| itm.key()).collect();
+        let opts = prometheus::HistogramOpts::new(key.name(), key.name()).buckets(buckets);
+
+        let result = prometheus::HistogramVec::new(opts, &label_names)
+            .map_err(|err| err.to_string())
+            .and_then(|histogram| {
+                self.inner
+                    .try_register_metric(histogram)
+                    .map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            my_logger::LOGGER.write_error(
+                "SdkMetricsRecorder".to_string(),
+                format!(
+                    "Can not apply buckets to histogram {}. Err: {}",
+                    key.name(),
+                    err
+                ),
+                my_logger::LogEventCtx::new(),
+            );
+        }
+    }
+}
+
+impl Recorder for SdkMetricsRecorder {
+    fn describe_counter(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner
+            .describe_counter(self.get_key_name(key), unit, description)
+    }
+
+    fn describe_gauge(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner
+            .describe_gauge(self.get_key_name(key), unit, description)
+    }
+
+    fn describe_histogram(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner
+            .describe_histogram(self.get_key_name(key), unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        let config = self.config.load();
+        self.inner
+            .register_counter(&self.get_key(&config, key), metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        let config = self.config.load();
+        self.inner
+            .register_gauge(&self.get_key(&config, key), metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        let config = self.config.load();
+        let sdk_key = self.get_key(&config, key);
+
+        if !config.histogram_buckets.is_empty() {
+            self.register_buckets(&config, key.name(), &sdk_key);
+        }
+
+        self.inner.register_histogram(&sdk_key, metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HistogramBuckets;
+
+    fn get_config(config: MetricsConfig) -> &'static ArcSwap<MetricsConfig> {
+        Box::leak(Box::new(ArcSwap::from_pointee(config)))
+    }
+
+    fn get_labels(key: &Key) -> Vec<(String, String)> {
+        let mut result: Vec<(String, String)> = key
+            .labels()
+            .map(|itm| (itm.key().to_string(), itm.value().to_string()))
+            .collect();
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn test_call_site_labels_win_over_constant_labels() {
+        let config = MetricsConfig {
+            prefix: Some("sdk".to_string()),
+            constant_labels: [
+                ("app".to_string(), "orders".to_string()),
+                ("env".to_string(), "prod".to_string()),
+            ]
+            .into(),
+            histogram_buckets: vec![],
+        };
+        let recorder = SdkMetricsRecorder::new(&prometheus::Registry::new(), get_config(config));
+
+        let key = Key::from_parts(
+            "http_request_count",
+            vec![Label::new("env", "test"), Label::new("method", "GET")],
+        );
+        let sdk_key = recorder.get_key(&recorder.config.load(), &key);
+
+        assert_eq!(sdk_key.name(), "sdk_http_request_count");
+        assert_eq!(
+            get_labels(&sdk_key),
+            vec![
+                ("app".to_string(), "orders".to_string()),
+                ("env".to_string(), "test".to_string()),
+                ("method".to_string(), "GET".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_key_without_config_is_kept() {
+        let recorder = SdkMetricsRecorder::new(
+            &prometheus::Registry::new(),
+            get_config(MetricsConfig::default()),
+        );
+
+        let key = Key::from_parts("http_request_count", vec![Label::new("method", "GET")]);
+
+        assert_eq!(recorder.get_key(&recorder.config.load(), &key), key);
+    }
+
+    #[test]
+    fn test_histograms_get_configured_buckets() {
+        let config = MetricsConfig {
+            prefix: Some("sdk".to_string()),
+            constant_labels: [("env".to_string(), "test".to_string())].into(),
+            histogram_buckets: vec![
+                HistogramBuckets::new("*_duration_sec", &[0.5, 0.1]),
+                HistogramBuckets::new("*", &[10.0]),
+            ],
+        };
+        let registry = prometheus::Registry::new();
+        let recorder = SdkMetricsRecorder::new(&registry, get_config(config));
+
+        metrics::with_local_recorder(&recorder, || {
+            metrics::histogram!("http_request_duration_sec", "method" => "GET").record(0.3);
+        });
+
+        let families = registry.gather();
+        assert_eq!(families.len(), 1);
+        assert_eq!(families[0].get_name(), "sdk_http_request_duration_sec");
+
+        let metric = &families[0].get_metric()[0];
+        let labels: Vec<(&str, &str)> = metric
+            .get_label()
+            .iter()
+            .map(|itm| (itm.get_name(), itm.get_value()))
+            .collect();
+        assert_eq!(labels, vec![("env", "test"), ("method", "GET")]);
+
+        let buckets: Vec<(f64, u64)> = metric
+            .get_histogram()
+            .get_bucket()
+            .iter()
+            .map(|itm| (itm.get_upper_bound(), itm.get_cumulative_count()))
+            .collect();
+        assert_eq!(buckets, vec![(0.1, 0), (0.5, 1)]);
+    }
+}
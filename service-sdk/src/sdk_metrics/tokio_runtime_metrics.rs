@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rust_extensions::MyTimerTick;
+use tokio::runtime::Handle;
+
+pub const TOKIO_METRICS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Metrics marked as unstable by tokio are published only when the service is built with `--cfg tokio_unstable`.
+pub(crate) struct TokioRuntimeMetricsTimerTick {
+    pub handle: Handle,
+}
+
+#[async_trait]
+impl MyTimerTick for TokioRuntimeMetricsTimerTick {
+    async fn tick(&self) {
+        let runtime_metrics = self.handle.metrics();
+        let num_workers = runtime_metrics.num_workers();
+
+        metrics::gauge!("tokio_workers").set(num_workers as f64);
+        metrics::gauge!("tokio_alive_tasks").set(runtime_metrics.num_alive_tasks() as f64);
+        metrics::gauge!("tokio_global_queue_depth")
+            .set(runtime_metrics.global_queue_depth() as f64);
+
+        for worker in 0..num_workers {
+            let labels = &[("worker", worker.to_string())];
+
+            #[cfg(target_has_atomic = "64")]
+            {
+                metrics::gauge!("tokio_worker_busy_seconds_total", labels).set(
+                    runtime_metrics
+                        .worker_total_busy_duration(worker)
+                        .as_secs_f64(),
+                );
+                metrics::counter!("tokio_worker_park_count", labels)
+                    .absolute(runtime_metrics.worker_park_count(worker));
+            }
+
+            #[cfg(tokio_unstable)]
+            {
+                metrics::gauge!("tokio_worker_local_queue_depth", labels)
+                    .set(runtime_metrics.worker_local_queue_depth(worker) as f64);
+                metrics::counter!("tokio_worker_poll_count", labels)
+                    .absolute(runtime_metrics.worker_poll_count(worker));
+                metrics::counter!("tokio_worker_steal_count", labels)
+                    .absolute(runtime_metrics.worker_steal_count(worker));
+                metrics::gauge!("tokio_worker_mean_poll_time_sec", labels)
+                    .set(runtime_metrics.worker_mean_poll_time(worker).as_secs_f64());
+            }
+        }
+
+        #[cfg(tokio_unstable)]
+        publish_unstable_metrics(&runtime_metrics);
+    }
+}
+
+#[cfg(tokio_unstable)]
+fn publish_unstable_metrics(runtime_metrics: &tokio::runtime::RuntimeMetrics) {
+    metrics::gauge!("tokio_blocking_threads").set(runtime_metrics.num_blocking_threads() as f64);
+    metrics::gauge!("tokio_idle_blocking_threads")
+        .set(runtime_metrics.num_idle_blocking_threads() as f64);
+    metrics::gauge!("tokio_blocking_queue_depth")
+        .set(runtime_metrics.blocking_queue_depth() as f64);
+    metrics::counter!("tokio_spawned_tasks_count").absolute(runtime_metrics.spawned_tasks_count());
+    metrics::counter!("tokio_remote_schedule_count")
+        .absolute(runtime_metrics.remote_schedule_count());
+
+    // Requires `enable_metrics_poll_time_histogram` on the runtime builder.
+    if !runtime_metrics.poll_time_histogram_enabled() {
+        return;
+    }
+
+    if let Some(poll_time_histogram) = get_poll_time_histogram() {
+        poll_time_histogram.update(runtime_metrics);
+    }
+}
+
+#[cfg(tokio_unstable)]
+static POLL_TIME_HISTOGRAM: std::sync::OnceLock<Option<PollTimeHistogram>> =
+    std::sync::OnceLock::new();
+
+#[cfg(tokio_unstable)]
+fn get_poll_time_histogram() -> Option<&'static PollTimeHistogram> {
+    POLL_TIME_HISTOGRAM
+        .get_or_init(|| {
+            let result = PollTimeHistogram::register();
+
+            if let Err(err) = &result {
+                my_logger::LOGGER.write_error(
+                    "TokioRuntimeMetrics".to_string(),
+                    format!(
+                        "Can not register tokio_poll_time_sec histogram. Err: {}",
+                        err
+                    ),
+                    my_logger::LogEventCtx::new(),
+                );
+            }
+
+            result.ok()
+        })
+        .as_ref()
+}
+
+/// Tokio keeps the poll time histogram itself, so its snapshot is exported as a histogram family
+/// instead of being observed into a prometheus histogram.
+#[cfg(tokio_unstable)]
+#[derive(Clone)]
+struct PollTimeHistogram {
+    desc: prometheus::core::Desc,
+    snapshot: std::sync::Arc<std::sync::Mutex<Option<prometheus::proto::Histogram>>>,
+}
+
+#[cfg(tokio_unstable)]
+impl PollTimeHistogram {
+    fn register() -> Result<Self, String> {
+        let desc = super::get_prometheus_opts("tokio_poll_time_sec", &[])
+            .describe()
+            .map_err(|err| err.to_string())?;
+
+        let result = Self {
+            desc,
+            snapshot: Default::default(),
+        };
+
+        prometheus::default_registry()
+            .register(Box::new(result.clone()))
+            .map_err(|err| err.to_string())?;
+
+        Ok(result)
+    }
+
+    // Buckets are the same for every worker, so counts are summed up. Tokio buckets are not
+    // cumulative; the last one is open-ended and is rendered as `+Inf` from the sample count.
+    fn update(&self, runtime_metrics: &tokio::runtime::RuntimeMetrics) {
+        let num_buckets = runtime_metrics.poll_time_histogram_num_buckets();
+        let num_workers = runtime_metrics.num_workers();
+
+        let mut buckets = Vec::with_capacity(num_buckets);
+        let mut cumulative_count = 0;
+
+        for bucket in 0..num_buckets {
+            cumulative_count += (0..num_workers)
+                .map(|worker| runtime_metrics.poll_time_histogram_bucket_count(worker, bucket))
+                .sum::<u64>();
+
+            if bucket + 1 == num_buckets {
+                break;
+            }
+
+            let mut proto_bucket = prometheus::proto::Bucket::default();
+            proto_bucket.set_upper_bound(
+                runtime_metrics
+                    .poll_time_histogram_bucket_range(bucket)
+                    .end
+                    .as_secs_f64(),
+            );
+            proto_bucket.set_cumulative_count(cumulative_count);
+            buckets.push(proto_bucket);
+        }
+
+        let mut histogram = prometheus::proto::Histogram::default();
+        histogram.set_bucket(buckets.into());
+        histogram.set_sample_count(cumulative_count);
+        // Tokio does not sum up poll times, busy time of the workers is the closest value.
+        #[cfg(target_has_atomic = "64")]
+        histogram.set_sample_sum(
+            (0..num_workers)
+                .map(|worker| {
+                    runtime_metrics
+                        .worker_total_busy_duration(worker)
+                        .as_secs_f64()
+                })
+                .sum(),
+        );
+
+        *self.snapshot.lock().unwrap() = Some(histogram);
+    }
+}
+
+#[cfg(tokio_unstable)]
+impl prometheus::core::Collector for PollTimeHistogram {
+    fn desc(&self) -> Vec<&prometheus::core::Desc> {
+        vec![&self.desc]
+    }
+
+    fn collect(&self) -> Vec<prometheus::proto::MetricFamily> {
+        let Some(histogram) = self.snapshot.lock().unwrap().clone() else {
+            return vec![];
+        };
+
+        let mut metric = prometheus::proto::Metric::default();
+        metric.set_label(self.desc.const_label_pairs.clone().into());
+        metric.set_histogram(histogram);
+
+        let mut family = prometheus::proto::MetricFamily::default();
+        family.set_name(self.desc.fq_name.clone());
+        family.set_help(self.desc.help.clone());
+        family.set_field_type(prometheus::proto::MetricType::HISTOGRAM);
+        family.set_metric(vec![metric].into());
+
+        vec![family]
+    }
+}
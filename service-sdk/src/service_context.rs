@@ -20,12 +20,20 @@ use my_service_bus::{
     client::{MyServiceBusClient, MyServiceBusSettings},
 };
 
-use std::{sync::Arc, time::Duration};
-
-use crate::{EventsPerSecondCounter, EventsPerSecondTimerTick, HttpServerBuilder, ServiceInfo};
+use std::{future::Future, sync::Arc, time::Duration};
+
+use crate::{
+    AppStateReadinessCheck, EventsCounterOptions, EventsPerSecondCounter, EventsPerSecondTimerTick,
+    EventsWindow, GracefulShutdown, HealthCheck, HealthCheckOptions, HealthChecks,
+    HealthChecksTimerTick, HttpServerBuilder, LatencyTracker, LatencyTrackersTimerTick,
+    MetricsConfig, ProcessMetricsTimerTick, ReadinessCheck, ReadinessChecks, RunningService,
+    SdkMetricsRecorder, ServiceInfo, ShutdownHooks, StartupError, UnixSocketConfig,
+    DEFAULT_HTTP_START_TIMEOUT, DEFAULT_SHUTDOWN_DRAIN_TIMEOUT, DEFAULT_SHUTDOWN_FLUSH_TIMEOUT,
+    DEFAULT_SHUTDOWN_HOOK_TIMEOUT, PROCESS_METRICS_INTERVAL,
+};
 
 #[cfg(feature = "grpc")]
-use crate::GrpcServerBuilder;
+use crate::{GrpcHealthTimerTick, GrpcServerBuilder};
 
 pub struct ServiceContext {
     pub http_server_builder: HttpServerBuilder,
@@ -38,17 +46,29 @@ pub struct ServiceContext {
     pub background_timers: Vec<MyTimer>,
     pub background_exact_timers: Vec<MyExactTimer>,
     events_per_second_counters: Arc<ArcSwap<Vec<Arc<EventsPerSecondCounter>>>>,
+    latency_trackers: Arc<ArcSwap<Vec<Arc<LatencyTracker>>>>,
+    pub graceful_shutdown: Arc<GracefulShutdown>,
+    shutdown_drain_timeout: Duration,
+    shutdown_flush_timeout: Duration,
+    shutdown_hooks: ShutdownHooks,
+    pub readiness_checks: Arc<ReadinessChecks>,
+    pub health_checks: Arc<HealthChecks>,
+    // Applied to the gRPC server builder once `configure_grpc_server` creates it.
+    #[cfg(all(unix, feature = "grpc"))]
+    unix_socket_config: UnixSocketConfig,
     #[cfg(feature = "my-nosql-data-reader-sdk")]
     pub my_no_sql_connection: Arc<MyNoSqlTcpConnection>,
     #[cfg(feature = "my-service-bus")]
     pub sb_client: Arc<MyServiceBusClient>,
     #[cfg(feature = "grpc")]
     pub grpc_server_builder: Option<GrpcServerBuilder>,
+    #[cfg(all(feature = "grpc", feature = "with-tls"))]
+    grpc_tls_settings: Option<crate::TlsSettings>,
 }
 
 impl ServiceContext {
     pub async fn new(settings_reader: service_sdk_macros::generate_settings_signature!()) -> Self {
-        metrics_prometheus::install();
+        SdkMetricsRecorder::install();
 
         #[cfg(feature = "with-tls")]
         rustls::crypto::ring::default_provider()
@@ -93,8 +113,76 @@ impl ServiceContext {
             }),
         );
 
+        let latency_trackers: Arc<ArcSwap<Vec<Arc<LatencyTracker>>>> =
+            Arc::new(ArcSwap::from_pointee(Vec::new()));
+
+        let mut latency_trackers_timer = MyTimer::new(Duration::from_secs(1));
+        latency_trackers_timer.register_timer(
+            "LatencyTrackers",
+            Arc::new(LatencyTrackersTimerTick {
+                trackers: latency_trackers.clone(),
+            }),
+        );
+
+        let mut process_metrics_timer = MyTimer::new(PROCESS_METRICS_INTERVAL);
+        process_metrics_timer.set_first_tick_before_delay();
+        process_metrics_timer.register_timer(
+            "ProcessMetrics",
+            Arc::new(ProcessMetricsTimerTick {
+                app_name,
+                app_version,
+            }),
+        );
+
+        let graceful_shutdown = Arc::new(GracefulShutdown::new());
+
+        let readiness_checks = Arc::new(ReadinessChecks::new());
+        readiness_checks.register(Arc::new(AppStateReadinessCheck {
+            app_states: app_states.clone(),
+            graceful_shutdown: graceful_shutdown.clone(),
+        }));
+
+        #[cfg(feature = "my-service-bus")]
+        readiness_checks.register(Arc::new(crate::SbClientReadinessCheck {
+            sb_client: sb_client.clone(),
+        }));
+
+        let health_checks = Arc::new(HealthChecks::new());
+
+        let mut health_checks_timer = MyTimer::new(Duration::from_secs(1));
+        health_checks_timer.register_timer(
+            "HealthChecks",
+            Arc::new(HealthChecksTimerTick {
+                health_checks: health_checks.clone(),
+            }),
+        );
+
+        #[allow(unused_mut)]
+        let mut background_timers = vec![
+            events_per_second_timer,
+            latency_trackers_timer,
+            process_metrics_timer,
+            health_checks_timer,
+        ];
+
+        #[cfg(feature = "tokio-runtime-metrics")]
+        {
+            let mut tokio_metrics_timer = MyTimer::new(crate::TOKIO_METRICS_INTERVAL);
+            tokio_metrics_timer.register_timer(
+                "TokioRuntimeMetrics",
+                Arc::new(crate::TokioRuntimeMetricsTimerTick {
+                    handle: tokio::runtime::Handle::current(),
+                }),
+            );
+            background_timers.push(tokio_metrics_timer);
+        }
+
+        let mut http_server_builder = HttpServerBuilder::new(app_name, app_version);
+        http_server_builder.set_readiness_checks(readiness_checks.clone());
+        http_server_builder.set_health_checks(health_checks.clone());
+
         Self {
-            http_server_builder: HttpServerBuilder::new(app_name, app_version),
+            http_server_builder,
             http_servers: vec![],
             telemetry_writer: MyTelemetryWriter::new(app_name, settings_reader.clone()),
             app_states,
@@ -106,17 +194,106 @@ impl ServiceContext {
             app_version,
             #[cfg(feature = "grpc")]
             grpc_server_builder: None,
-            background_timers: vec![events_per_second_timer],
+            background_timers,
             background_exact_timers: vec![],
             events_per_second_counters,
+            latency_trackers,
+            graceful_shutdown,
+            shutdown_drain_timeout: DEFAULT_SHUTDOWN_DRAIN_TIMEOUT,
+            shutdown_flush_timeout: DEFAULT_SHUTDOWN_FLUSH_TIMEOUT,
+            shutdown_hooks: ShutdownHooks::default(),
+            readiness_checks,
+            health_checks,
+            #[cfg(all(unix, feature = "grpc"))]
+            unix_socket_config: UnixSocketConfig::default(),
+            #[cfg(all(feature = "grpc", feature = "with-tls"))]
+            grpc_tls_settings: None,
         }
     }
 
+    pub fn set_shutdown_drain_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.shutdown_drain_timeout = timeout;
+        self
+    }
+
+    pub fn set_shutdown_flush_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.shutdown_flush_timeout = timeout;
+        self
+    }
+
+    /// Must be called before any metric is published, so before `start_application`.
+    pub fn set_metrics_config(&mut self, metrics_config: MetricsConfig) -> &mut Self {
+        crate::set_metrics_config(metrics_config);
+        self
+    }
+
+    /// Applies the certificates of the settings model to the gRPC TCP listener.
+    /// Certificates set in `configure_grpc_server` afterwards take precedence.
+    #[cfg(all(feature = "grpc", feature = "with-tls"))]
+    pub async fn set_tls_from_settings(
+        &mut self,
+        settings_reader: &impl crate::TlsSettingsReader,
+    ) -> &mut Self {
+        if let Some(grpc_tls_settings) = settings_reader.get_grpc_tls_settings().await {
+            if let Some(grpc_server_builder) = self.grpc_server_builder.as_mut() {
+                grpc_server_builder.set_tls_settings(grpc_tls_settings.clone());
+            }
+            self.grpc_tls_settings = Some(grpc_tls_settings);
+        }
+
+        self
+    }
+
+    /// Can be called at any point before `start_application`; registered actions and services are kept.
+    pub fn set_unix_socket_config(&mut self, unix_socket_config: UnixSocketConfig) -> &mut Self {
+        #[cfg(unix)]
+        {
+            #[cfg(feature = "grpc")]
+            {
+                if let Some(grpc_server_builder) = self.grpc_server_builder.as_mut() {
+                    grpc_server_builder.set_unix_socket_config(unix_socket_config.clone());
+                }
+
+                self.unix_socket_config = unix_socket_config.clone();
+            }
+
+            self.http_server_builder
+                .set_unix_socket_config(unix_socket_config);
+        }
+
+        #[cfg(not(unix))]
+        let _ = unix_socket_config;
+
+        self
+    }
+
     pub fn register_events_per_second(
         &self,
         metric_name: impl Into<String>,
     ) -> Arc<EventsPerSecondCounter> {
-        let counter = Arc::new(EventsPerSecondCounter::new(metric_name));
+        self.register_events_counter(metric_name, EventsWindow::PerSecond)
+    }
+
+    pub fn register_events_counter(
+        &self,
+        metric_name: impl Into<String>,
+        window: EventsWindow,
+    ) -> Arc<EventsPerSecondCounter> {
+        self.register_events_counter_with_options(
+            metric_name,
+            EventsCounterOptions {
+                window,
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn register_events_counter_with_options(
+        &self,
+        metric_name: impl Into<String>,
+        options: EventsCounterOptions,
+    ) -> Arc<EventsPerSecondCounter> {
+        let counter = Arc::new(EventsPerSecondCounter::new(metric_name, options));
         self.events_per_second_counters.rcu(|prev| {
             let mut new: Vec<Arc<EventsPerSecondCounter>> = (**prev).clone();
             new.push(counter.clone());
@@ -125,6 +302,95 @@ impl ServiceContext {
         counter
     }
 
+    /// `buckets` are in seconds. Registering the same name again returns the registered tracker.
+    pub fn register_latency_tracker(
+        &self,
+        metric_name: impl Into<String>,
+        label_names: &[&str],
+        buckets: &[f64],
+    ) -> Arc<LatencyTracker> {
+        let metric_name = metric_name.into();
+
+        let registered = self
+            .latency_trackers
+            .load()
+            .iter()
+            .find(|itm| itm.get_metric_name() == metric_name)
+            .cloned();
+
+        if let Some(tracker) = registered {
+            if !tracker.has_same_metrics(label_names, buckets) {
+                panic!(
+                    "Latency tracker {} is already registered with other label names or buckets",
+                    metric_name
+                );
+            }
+            return tracker;
+        }
+
+        let tracker = Arc::new(LatencyTracker::new(metric_name, label_names, buckets));
+        self.latency_trackers.rcu(|prev| {
+            let mut new: Vec<Arc<LatencyTracker>> = (**prev).clone();
+            new.push(tracker.clone());
+            Arc::new(new)
+        });
+        tracker
+    }
+
+    pub fn register_readiness_check(
+        &self,
+        check: Arc<dyn ReadinessCheck + Send + Sync + 'static>,
+    ) -> &Self {
+        self.readiness_checks.register(check);
+        self
+    }
+
+    /// `name` tells connections apart in the readiness report: `postgres:<name>`.
+    #[cfg(feature = "postgres")]
+    pub fn register_postgres_readiness_check(
+        &self,
+        name: &str,
+        postgres: Arc<my_postgres::MyPostgres>,
+    ) -> &Self {
+        self.register_readiness_check(Arc::new(crate::PostgresReadinessCheck::new(name, postgres)))
+    }
+
+    pub fn register_health_check(
+        &self,
+        name: impl Into<String>,
+        options: HealthCheckOptions,
+        check: Arc<dyn HealthCheck + Send + Sync + 'static>,
+    ) -> &Self {
+        self.health_checks.register(name, options, check);
+        self
+    }
+
+    pub fn register_shutdown_hook<TFuture: Future<Output = ()> + Send + 'static>(
+        &mut self,
+        name: impl Into<String>,
+        priority: i32,
+        hook: impl FnOnce() -> TFuture + Send + 'static,
+    ) -> &mut Self {
+        self.register_shutdown_hook_with_timeout(
+            name,
+            priority,
+            DEFAULT_SHUTDOWN_HOOK_TIMEOUT,
+            hook,
+        )
+    }
+
+    pub fn register_shutdown_hook_with_timeout<TFuture: Future<Output = ()> + Send + 'static>(
+        &mut self,
+        name: impl Into<String>,
+        priority: i32,
+        timeout: Duration,
+        hook: impl FnOnce() -> TFuture + Send + 'static,
+    ) -> &mut Self {
+        self.shutdown_hooks
+            .register(name.into(), priority, timeout, hook);
+        self
+    }
+
     pub fn register_timer(&mut self, duration: Duration, builder: impl Fn(&mut MyTimer)) {
         let mut timer = MyTimer::new(duration);
         builder(&mut timer);
@@ -149,6 +415,53 @@ impl ServiceContext {
     }
 
     pub async fn start_application(&mut self) {
+        match self.try_start_application().await {
+            Ok(running_service) => running_service.wait_until_shutdown().await,
+            Err(err) => panic!("Failed to start application: {}", err),
+        }
+    }
+
+    pub async fn try_start_application(&mut self) -> Result<RunningService<'_>, StartupError> {
+        self.http_server_builder.bind()?;
+
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_server_builder) = self.grpc_server_builder.as_mut() {
+            grpc_server_builder.bind(self.app_name)?;
+
+            #[cfg(feature = "with-tls")]
+            if let Some(tls_config) = grpc_server_builder.get_tls_config() {
+                let mut tls_reload_timer = MyTimer::new(crate::TLS_RELOAD_INTERVAL);
+                tls_reload_timer.register_timer(
+                    "GrpcTlsReload",
+                    Arc::new(crate::TlsReloadTimerTick { tls_config }),
+                );
+                self.background_timers.push(tls_reload_timer);
+            }
+
+            if let Some(grpc_health_status) = grpc_server_builder.get_health_status() {
+                grpc_health_status.set_serving(false).await;
+
+                let health_checks = if grpc_server_builder.health_checks_are_mirrored() {
+                    Some(self.health_checks.clone())
+                } else {
+                    None
+                };
+
+                let mut grpc_health_timer = MyTimer::new(Duration::from_secs(1));
+                grpc_health_timer.set_first_tick_before_delay();
+                grpc_health_timer.register_timer(
+                    "GrpcHealthStatus",
+                    Arc::new(GrpcHealthTimerTick {
+                        grpc_health_status,
+                        app_states: self.app_states.clone(),
+                        graceful_shutdown: self.graceful_shutdown.clone(),
+                        health_checks,
+                    }),
+                );
+                self.background_timers.push(grpc_health_timer);
+            }
+        }
+
         self.app_states.set_initialized();
         self.telemetry_writer
             .start(self.app_states.clone(), my_logger::LOGGER.clone());
@@ -163,21 +476,88 @@ impl ServiceContext {
         #[cfg(feature = "my-service-bus")]
         self.sb_client.start().await;
 
-        let mut http_servers = self.http_server_builder.build();
+        self.http_servers = self.http_server_builder.start(&self.app_states);
 
-        for http_server in http_servers.iter_mut() {
-            http_server.start_auto(self.app_states.clone(), my_logger::LOGGER.clone());
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_server_builder) = self.grpc_server_builder.as_mut() {
+            grpc_server_builder.start(&self.graceful_shutdown);
         }
 
-        self.http_servers = http_servers;
+        self.http_server_builder
+            .wait_until_listening(DEFAULT_HTTP_START_TIMEOUT)
+            .await?;
+
+        println!("Application is stated");
+        Ok(RunningService::new(self))
+    }
+
+    pub(crate) async fn shutdown(&mut self) {
+        println!("Application is shutting down");
+        my_logger::LOGGER.write_info(
+            "Shutdown".to_string(),
+            "Shutdown is requested. Draining in-flight requests".to_string(),
+            my_logger::LogEventCtx::new(),
+        );
+
+        self.graceful_shutdown.set_not_ready();
 
         #[cfg(feature = "grpc")]
-        if let Some(grpc_server_builder) = self.grpc_server_builder.as_mut() {
-            grpc_server_builder.start(self.app_name);
+        if let Some(grpc_server_builder) = self.grpc_server_builder.as_ref() {
+            if let Some(grpc_health_status) = grpc_server_builder.get_health_status() {
+                grpc_health_status.stop_serving().await;
+            }
         }
 
-        println!("Application is stated");
-        self.app_states.wait_until_shutdown().await;
+        self.graceful_shutdown.request();
+
+        // HTTP and gRPC drain concurrently, so the whole drain stays within one timeout.
+        let drain_timeout = self.shutdown_drain_timeout;
+        let drain_http = self.http_server_builder.wait_until_stopped(drain_timeout);
+
+        #[cfg(feature = "grpc")]
+        let grpc_server_builder = self.grpc_server_builder.as_mut();
+        #[cfg(feature = "grpc")]
+        let drain_grpc = async move {
+            if let Some(grpc_server_builder) = grpc_server_builder {
+                grpc_server_builder.wait_until_stopped(drain_timeout).await;
+            }
+        };
+
+        #[cfg(feature = "grpc")]
+        tokio::join!(drain_http, drain_grpc);
+        #[cfg(not(feature = "grpc"))]
+        drain_http.await;
+
+        self.shutdown_hooks.run().await;
+
+        #[cfg(feature = "my-service-bus")]
+        self.sb_client.stop().await;
+
+        self.app_states.set_shutting_down();
+
+        #[cfg(unix)]
+        {
+            self.http_server_builder.remove_unix_socket();
+
+            #[cfg(feature = "grpc")]
+            if let Some(grpc_server_builder) = self.grpc_server_builder.as_ref() {
+                grpc_server_builder.remove_unix_socket();
+            }
+        }
+
+        my_logger::LOGGER.write_info(
+            "Shutdown".to_string(),
+            "Application is stopped. Flushing telemetry and logs".to_string(),
+            my_logger::LogEventCtx::new(),
+        );
+
+        let telemetry_writer = &self.telemetry_writer;
+        let _ = tokio::time::timeout(self.shutdown_flush_timeout, async {
+            telemetry_writer.flush().await;
+            my_logger::LOGGER.flush().await;
+        })
+        .await;
+        println!("Application is stopped");
     }
 
     //ns
@@ -191,7 +571,11 @@ impl ServiceContext {
     >(
         &self,
     ) -> Arc<my_no_sql_sdk::reader::MyNoSqlDataReaderTcp<TMyNoSqlEntity>> {
-        self.my_no_sql_connection.get_reader()
+        let reader = self.my_no_sql_connection.get_reader();
+        // Readers of the same table share the check: it is registered once per table name.
+        let readiness_check = crate::NoSqlReaderReadinessCheck::new(reader.clone());
+        self.readiness_checks.register(Arc::new(readiness_check));
+        reader
     }
 
     //sb
@@ -279,6 +663,12 @@ impl ServiceContext {
             }
             None => {
                 let mut grpc_server_builder = GrpcServerBuilder::new();
+                #[cfg(unix)]
+                grpc_server_builder.set_unix_socket_config(self.unix_socket_config.clone());
+                #[cfg(feature = "with-tls")]
+                if let Some(grpc_tls_settings) = self.grpc_tls_settings.clone() {
+                    grpc_server_builder.set_tls_settings(grpc_tls_settings);
+                }
                 config(&mut grpc_server_builder);
                 self.grpc_server_builder = Some(grpc_server_builder);
             }
@@ -0,0 +1,95 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use rust_extensions::AppStates;
+use tokio::sync::watch;
+
+pub const DEFAULT_SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+pub const DEFAULT_SHUTDOWN_FLUSH_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub struct GracefulShutdown {
+    not_ready: AtomicBool,
+    requested: AtomicBool,
+    sender: watch::Sender<bool>,
+}
+
+impl Default for GracefulShutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GracefulShutdown {
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self {
+            not_ready: AtomicBool::new(false),
+            requested: AtomicBool::new(false),
+            sender,
+        }
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+
+    pub fn is_ready(&self) -> bool {
+        !self.not_ready.load(Ordering::Relaxed) && !self.is_requested()
+    }
+
+    /// Readiness turns off first, so load balancers stop routing before servers stop accepting.
+    pub fn set_not_ready(&self) {
+        self.not_ready.store(true, Ordering::Relaxed);
+    }
+
+    pub fn request(&self) {
+        self.set_not_ready();
+        self.requested.store(true, Ordering::Relaxed);
+        self.sender.send_replace(true);
+    }
+
+    /// Resolves once shutdown is requested. Servers use it to stop accepting new connections.
+    pub fn wait(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut receiver = self.sender.subscribe();
+        async move {
+            let _ = receiver.wait_for(|requested| *requested).await;
+        }
+    }
+}
+
+pub(crate) async fn wait_for_shutdown_signal(app_states: &AppStates) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sigterm =
+            signal(SignalKind::terminate()).expect("Failed to subscribe to SIGTERM signal");
+
+        tokio::select! {
+            _ = sigterm.recv() => {
+                log_signal("SIGTERM");
+            }
+            _ = tokio::signal::ctrl_c() => {
+                log_signal("SIGINT");
+            }
+            _ = app_states.wait_until_shutdown() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {
+            log_signal("SIGINT");
+        }
+        _ = app_states.wait_until_shutdown() => {}
+    }
+}
+
+fn log_signal(signal: &str) {
+    my_logger::LOGGER.write_info(
+        "Shutdown".to_string(),
+        format!("{} received", signal),
+        my_logger::LogEventCtx::new(),
+    );
+}
@@ -0,0 +1,4 @@
+mod graceful_shutdown;
+pub use graceful_shutdown::*;
+mod shutdown_hooks;
+pub use shutdown_hooks::*;
@@ -0,0 +1,239 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use my_logger::LogEventCtx;
+
+pub const DEFAULT_SHUTDOWN_HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+type ShutdownHookCallback =
+    Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + 'static>;
+
+struct ShutdownHook {
+    name: String,
+    priority: i32,
+    timeout: Duration,
+    callback: ShutdownHookCallback,
+}
+
+#[derive(Default)]
+pub(crate) struct ShutdownHooks {
+    hooks: Vec<ShutdownHook>,
+}
+
+impl ShutdownHooks {
+    pub fn register<TFuture: Future<Output = ()> + Send + 'static>(
+        &mut self,
+        name: String,
+        priority: i32,
+        timeout: Duration,
+        hook: impl FnOnce() -> TFuture + Send + 'static,
+    ) {
+        self.hooks.push(ShutdownHook {
+            name,
+            priority,
+            timeout,
+            callback: Box::new(move || Box::pin(hook())),
+        });
+    }
+
+    /// Runs hooks in ascending priority order. Hooks with the same priority run in registration order.
+    pub async fn run(&mut self) {
+        let mut hooks = std::mem::take(&mut self.hooks);
+        hooks.sort_by_key(|hook| hook.priority);
+
+        for hook in hooks {
+            let mut sw = stopwatch::Stopwatch::start_new();
+            let mut join_handle = tokio::spawn((hook.callback)());
+
+            let result = match tokio::time::timeout(hook.timeout, &mut join_handle).await {
+                Ok(Ok(())) => "ok",
+                Ok(Err(_)) => "panic",
+                Err(_) => {
+                    join_handle.abort();
+                    "timeout"
+                }
+            };
+
+            sw.stop();
+            let duration = sw.elapsed();
+
+            let ctx = LogEventCtx::new()
+                .add("hook", hook.name.as_str())
+                .add("priority", hook.priority.to_string());
+
+            if result == "ok" {
+                my_logger::LOGGER.write_info(
+                    "Shutdown hook".to_string(),
+                    format!("Shutdown hook {} finished in {:?}", hook.name, duration),
+                    ctx,
+                );
+            } else {
+                my_logger::LOGGER.write_error(
+                    "Shutdown hook".to_string(),
+                    format!(
+                        "Shutdown hook {} failed with result '{}' after {:?}",
+                        hook.name, result, duration
+                    ),
+                    ctx,
+                );
+            }
+
+            let labels = &[("hook", hook.name), ("result", result.to_string())];
+            metrics::histogram!("service_shutdown_hook_duration_sec", labels)
+                .record(duration.as_secs_f64());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use arc_swap::ArcSwap;
+
+    use super::*;
+    use crate::{MetricsConfig, SdkMetricsRecorder};
+
+    fn register_hook(
+        hooks: &mut ShutdownHooks,
+        name: &'static str,
+        priority: i32,
+        calls: &Arc<Mutex<Vec<&'static str>>>,
+    ) {
+        let calls = calls.clone();
+        hooks.register(
+            name.to_string(),
+            priority,
+            DEFAULT_SHUTDOWN_HOOK_TIMEOUT,
+            move || async move {
+                calls.lock().unwrap().push(name);
+            },
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hooks_run_in_priority_order() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut hooks = ShutdownHooks::default();
+
+        register_hook(&mut hooks, "flush-cache", 10, &calls);
+        register_hook(&mut hooks, "stop-consumers", -5, &calls);
+        register_hook(&mut hooks, "commit-offsets", 10, &calls);
+        register_hook(&mut hooks, "release-locks", 0, &calls);
+
+        hooks.run().await;
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "stop-consumers",
+                "release-locks",
+                "flush-cache",
+                "commit-offsets"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hook_above_timeout_is_aborted() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let slow_hook_finished = Arc::new(AtomicBool::new(false));
+        let mut hooks = ShutdownHooks::default();
+
+        let finished = slow_hook_finished.clone();
+        hooks.register(
+            "slow".to_string(),
+            0,
+            Duration::from_millis(50),
+            move || async move {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                finished.store(true, Ordering::SeqCst);
+            },
+        );
+        register_hook(&mut hooks, "next", 1, &calls);
+
+        let started = std::time::Instant::now();
+        hooks.run().await;
+
+        assert!(started.elapsed() < Duration::from_millis(300));
+        assert_eq!(*calls.lock().unwrap(), vec!["next"]);
+
+        // The aborted hook never gets to the end.
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(!slow_hook_finished.load(Ordering::SeqCst));
+    }
+
+    // The local recorder is seen by `run` because the current-thread runtime polls it on the test thread.
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_hook_results_are_recorded() {
+        let registry = prometheus::Registry::new();
+        let config: &'static ArcSwap<MetricsConfig> =
+            Box::leak(Box::new(ArcSwap::from_pointee(MetricsConfig::default())));
+        let recorder = SdkMetricsRecorder::new(&registry, config);
+        let _recorder_guard = metrics::set_default_local_recorder(&recorder);
+
+        let mut hooks = ShutdownHooks::default();
+        hooks.register(
+            "ok".to_string(),
+            0,
+            DEFAULT_SHUTDOWN_HOOK_TIMEOUT,
+            || async {},
+        );
+        hooks.register(
+            "timeout".to_string(),
+            1,
+            Duration::from_millis(20),
+            || async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            },
+        );
+        hooks.register(
+            "panic".to_string(),
+            2,
+            DEFAULT_SHUTDOWN_HOOK_TIMEOUT,
+            || async {
+                panic!("Hook failed");
+            },
+        );
+
+        hooks.run().await;
+
+        let families = registry.gather();
+        let family = families
+            .iter()
+            .find(|itm| itm.get_name() == "service_shutdown_hook_duration_sec")
+            .unwrap();
+
+        let mut results: Vec<(String, String, u64)> = family
+            .get_metric()
+            .iter()
+            .map(|metric| {
+                let get_label = |name: &str| {
+                    metric
+                        .get_label()
+                        .iter()
+                        .find(|itm| itm.get_name() == name)
+                        .map(|itm| itm.get_value().to_string())
+                        .unwrap_or_default()
+                };
+                (
+                    get_label("hook"),
+                    get_label("result"),
+                    metric.get_histogram().get_sample_count(),
+                )
+            })
+            .collect();
+        results.sort();
+
+        assert_eq!(
+            results,
+            vec![
+                ("ok".to_string(), "ok".to_string(), 1),
+                ("panic".to_string(), "panic".to_string(), 1),
+                ("timeout".to_string(), "timeout".to_string(), 1),
+            ]
+        );
+    }
+}
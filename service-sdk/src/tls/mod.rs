@@ -0,0 +1,6 @@
+mod tls_settings;
+pub use tls_settings::*;
+mod reloadable_tls_config;
+pub use reloadable_tls_config::*;
+mod tls_connect_info;
+pub use tls_connect_info::*;
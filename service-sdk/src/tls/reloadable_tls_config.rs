@@ -0,0 +1,371 @@
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use arc_swap::ArcSwap;
+use async_trait::async_trait;
+use my_logger::LogEventCtx;
+use rust_extensions::MyTimerTick;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+use crate::{StartupError, TlsSettings};
+
+pub const TLS_RELOAD_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Server TLS config which is rebuilt when certificate files change on disk.
+pub struct ReloadableTlsConfig {
+    listener: &'static str,
+    settings: TlsSettings,
+    alpn_protocols: Vec<Vec<u8>>,
+    server_config: ArcSwap<ServerConfig>,
+    files_modified: Mutex<Vec<Option<SystemTime>>>,
+}
+
+impl ReloadableTlsConfig {
+    pub fn load(
+        listener: &'static str,
+        settings: TlsSettings,
+        alpn_protocols: Vec<Vec<u8>>,
+    ) -> Result<Self, StartupError> {
+        let files_modified = get_files_modified(&settings);
+        let server_config = build_server_config(&settings, &alpn_protocols)
+            .map_err(|error| StartupError::Tls { listener, error })?;
+
+        Ok(Self {
+            listener,
+            settings,
+            alpn_protocols,
+            server_config: ArcSwap::from_pointee(server_config),
+            files_modified: Mutex::new(files_modified),
+        })
+    }
+
+    pub fn get_server_config(&self) -> Arc<ServerConfig> {
+        self.server_config.load_full()
+    }
+
+    pub fn get_acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.get_server_config())
+    }
+
+    pub fn client_auth_enabled(&self) -> bool {
+        self.settings.client_ca_path.is_some()
+    }
+
+    pub fn reload_if_changed(&self) {
+        let files_modified = get_files_modified(&self.settings);
+
+        if *self.files_modified.lock().unwrap() == files_modified {
+            return;
+        }
+
+        let ctx = LogEventCtx::new().add("listener", self.listener);
+
+        // Modification times are remembered only after a successful swap, so a half-written
+        // certificate is retried on the next tick instead of being skipped until the next change.
+        match build_server_config(&self.settings, &self.alpn_protocols) {
+            Ok(server_config) => {
+                self.server_config.store(Arc::new(server_config));
+                *self.files_modified.lock().unwrap() = files_modified;
+                my_logger::LOGGER.write_info(
+                    "TLS reload".to_string(),
+                    format!(
+                        "TLS certificates of {} listener are reloaded",
+                        self.listener
+                    ),
+                    ctx,
+                );
+            }
+            Err(err) => {
+                my_logger::LOGGER.write_error(
+                    "TLS reload".to_string(),
+                    format!(
+                        "Can not reload TLS certificates of {} listener. Previous certificates are kept. Err: {}",
+                        self.listener, err
+                    ),
+                    ctx,
+                );
+            }
+        }
+    }
+}
+
+pub(crate) struct TlsReloadTimerTick {
+    pub tls_config: Arc<ReloadableTlsConfig>,
+}
+
+#[async_trait]
+impl MyTimerTick for TlsReloadTimerTick {
+    async fn tick(&self) {
+        self.tls_config.reload_if_changed();
+    }
+}
+
+fn get_files_modified(settings: &TlsSettings) -> Vec<Option<SystemTime>> {
+    settings
+        .get_files()
+        .iter()
+        .map(|file| std::fs::metadata(file).and_then(|itm| itm.modified()).ok())
+        .collect()
+}
+
+fn build_server_config(
+    settings: &TlsSettings,
+    alpn_protocols: &[Vec<u8>],
+) -> Result<ServerConfig, String> {
+    let files = settings.get_files();
+
+    let certs = read_certs(files[0].as_str())?;
+
+    let key = std::fs::read(files[1].as_str())
+        .map_err(|err| format!("Can not read key file {}. Err: {}", files[1], err))?;
+    let key = PrivateKeyDer::from_pem_slice(&key)
+        .map_err(|err| format!("Can not parse key file {}. Err: {:?}", files[1], err))?;
+
+    let builder = ServerConfig::builder();
+
+    let server_config = match files.get(2) {
+        Some(client_ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(client_ca_path.as_str())? {
+                roots
+                    .add(cert)
+                    .map_err(|err| format!("Invalid client CA {}. Err: {}", client_ca_path, err))?;
+            }
+
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+                .build()
+                .map_err(|err| format!("Can not build client verifier. Err: {}", err))?;
+
+            builder
+                .with_client_cert_verifier(verifier)
+                .with_single_cert(certs, key)
+        }
+        None => builder.with_no_client_auth().with_single_cert(certs, key),
+    };
+
+    let mut server_config =
+        server_config.map_err(|err| format!("Invalid certificate or key. Err: {}", err))?;
+    server_config.alpn_protocols = alpn_protocols.to_vec();
+
+    Ok(server_config)
+}
+
+fn read_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+    let content =
+        std::fs::read(path).map_err(|err| format!("Can not read file {}. Err: {}", path, err))?;
+
+    let certs = CertificateDer::pem_slice_iter(&content)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("Can not parse certificates {}. Err: {:?}", path, err))?;
+
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {}", path));
+    }
+
+    Ok(certs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+    use rustls::pki_types::ServerName;
+    use rustls::ClientConfig;
+    use tokio_rustls::TlsConnector;
+
+    use super::*;
+
+    struct TestCa {
+        cert: rcgen::Certificate,
+        key: KeyPair,
+    }
+
+    impl TestCa {
+        fn new() -> Self {
+            let key = KeyPair::generate().unwrap();
+            let mut params = CertificateParams::new(vec![]).unwrap();
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            let cert = params.self_signed(&key).unwrap();
+            Self { cert, key }
+        }
+
+        /// Certificate and key in PEM.
+        fn issue(&self, name: &str) -> (String, String) {
+            let key = KeyPair::generate().unwrap();
+            let cert = CertificateParams::new(vec![name.to_string()])
+                .unwrap()
+                .signed_by(&key, &self.cert, &self.key)
+                .unwrap();
+            (cert.pem(), key.serialize_pem())
+        }
+
+        fn get_roots(&self) -> RootCertStore {
+            let mut roots = RootCertStore::empty();
+            roots.add(self.cert.der().clone()).unwrap();
+            roots
+        }
+    }
+
+    struct TestFiles {
+        dir: tempfile::TempDir,
+    }
+
+    impl TestFiles {
+        fn new() -> Self {
+            Self {
+                dir: tempfile::tempdir().unwrap(),
+            }
+        }
+
+        fn get_path(&self, name: &str) -> String {
+            self.dir.path().join(name).to_str().unwrap().to_string()
+        }
+
+        // Modification times are set explicitly: a rewrite within the same second must be seen as a change.
+        fn write(&self, name: &str, content: &str, modified_sec: u64) {
+            let path = self.get_path(name);
+            std::fs::write(&path, content).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(modified_sec))
+                .unwrap();
+        }
+
+        fn write_server_cert(&self, ca: &TestCa, modified_sec: u64) {
+            let (cert, key) = ca.issue("localhost");
+            self.write("server.crt", &cert, modified_sec);
+            self.write("server.key", &key, modified_sec);
+        }
+
+        fn get_settings(&self, client_ca: bool) -> TlsSettings {
+            let client_ca_path = self.get_path("ca.crt");
+            TlsSettings::new(
+                &self.get_path("server.crt"),
+                &self.get_path("server.key"),
+                client_ca.then_some(client_ca_path.as_str()),
+            )
+        }
+    }
+
+    #[test]
+    fn test_config_is_rebuilt_when_files_change() {
+        let ca = TestCa::new();
+        let files = TestFiles::new();
+        files.write_server_cert(&ca, 1_000);
+
+        let tls_config =
+            ReloadableTlsConfig::load("grpc", files.get_settings(false), vec![]).unwrap();
+        let loaded = tls_config.get_server_config();
+
+        tls_config.reload_if_changed();
+        assert!(Arc::ptr_eq(&loaded, &tls_config.get_server_config()));
+
+        files.write_server_cert(&ca, 2_000);
+        tls_config.reload_if_changed();
+        assert!(!Arc::ptr_eq(&loaded, &tls_config.get_server_config()));
+    }
+
+    #[test]
+    fn test_failed_reload_keeps_previous_config_and_is_retried() {
+        let ca = TestCa::new();
+        let files = TestFiles::new();
+        files.write_server_cert(&ca, 1_000);
+
+        let tls_config =
+            ReloadableTlsConfig::load("grpc", files.get_settings(false), vec![]).unwrap();
+        let loaded = tls_config.get_server_config();
+
+        // The new key is written, the certificate is still being written.
+        let (cert, key) = ca.issue("localhost");
+        files.write("server.key", &key, 2_000);
+        files.write("server.crt", "-----BEGIN CERTIFICATE-----", 2_000);
+        tls_config.reload_if_changed();
+        assert!(Arc::ptr_eq(&loaded, &tls_config.get_server_config()));
+
+        // The complete certificate keeps the modification times of the failed attempt: it is still picked up.
+        files.write("server.crt", &cert, 2_000);
+        tls_config.reload_if_changed();
+        assert!(!Arc::ptr_eq(&loaded, &tls_config.get_server_config()));
+    }
+
+    #[test]
+    fn test_invalid_certificate_fails_load() {
+        let files = TestFiles::new();
+        files.write("server.crt", "not a certificate", 1_000);
+        files.write("server.key", "not a key", 1_000);
+
+        let result = ReloadableTlsConfig::load("grpc", files.get_settings(false), vec![]);
+
+        assert!(matches!(
+            result,
+            Err(StartupError::Tls {
+                listener: "grpc",
+                ..
+            })
+        ));
+    }
+
+    async fn connect(
+        tls_config: &ReloadableTlsConfig,
+        client_config: ClientConfig,
+    ) -> Result<(), String> {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+
+        let connector = TlsConnector::from(Arc::new(client_config));
+        let server_name = ServerName::try_from("localhost").unwrap();
+
+        let (server_result, client_result) = tokio::join!(
+            tls_config.get_acceptor().accept(server_io),
+            connector.connect(server_name, client_io)
+        );
+
+        client_result.map_err(|err| format!("Client: {}", err))?;
+        server_result.map_err(|err| format!("Server: {}", err))?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_is_verified() {
+        let ca = TestCa::new();
+        let files = TestFiles::new();
+        files.write_server_cert(&ca, 1_000);
+        files.write("ca.crt", &ca.cert.pem(), 1_000);
+
+        let tls_config =
+            ReloadableTlsConfig::load("grpc", files.get_settings(true), vec![]).unwrap();
+        assert!(tls_config.client_auth_enabled());
+
+        let (client_cert, client_key) = ca.issue("client");
+        let client_config = ClientConfig::builder()
+            .with_root_certificates(ca.get_roots())
+            .with_client_auth_cert(
+                vec![CertificateDer::from_pem_slice(client_cert.as_bytes()).unwrap()],
+                PrivateKeyDer::from_pem_slice(client_key.as_bytes()).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(connect(&tls_config, client_config).await, Ok(()));
+
+        let client_config = ClientConfig::builder()
+            .with_root_certificates(ca.get_roots())
+            .with_no_client_auth();
+        assert!(connect(&tls_config, client_config).await.is_err());
+
+        // A certificate of another CA.
+        let (client_cert, client_key) = TestCa::new().issue("client");
+        let client_config = ClientConfig::builder()
+            .with_root_certificates(ca.get_roots())
+            .with_client_auth_cert(
+                vec![CertificateDer::from_pem_slice(client_cert.as_bytes()).unwrap()],
+                PrivateKeyDer::from_pem_slice(client_key.as_bytes()).unwrap(),
+            )
+            .unwrap();
+        assert!(connect(&tls_config, client_config).await.is_err());
+    }
+}
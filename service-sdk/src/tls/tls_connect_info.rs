@@ -0,0 +1,22 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use rustls::pki_types::CertificateDer;
+
+/// Connection info of a TLS connection. For gRPC it is available in request extensions.
+#[derive(Debug, Clone)]
+pub struct TlsConnectInfo {
+    pub local_addr: Option<SocketAddr>,
+    pub remote_addr: Option<SocketAddr>,
+    /// Certificate chain presented by the client. Set only when the listener requires mTLS.
+    pub peer_certificates: Option<Arc<Vec<CertificateDer<'static>>>>,
+}
+
+impl TlsConnectInfo {
+    /// Subject of the client certificate, e.g. `CN=service-a, O=MyCompany`.
+    pub fn get_peer_subject(&self) -> Option<String> {
+        let cert = self.peer_certificates.as_ref()?.first()?;
+        let (_, cert) = x509_parser::parse_x509_certificate(cert.as_ref()).ok()?;
+        Some(cert.subject().to_string())
+    }
+}
@@ -0,0 +1,46 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+
+/// Certificate files of a TLS listener. Can be a part of the settings model.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsSettings {
+    pub cert_path: String,
+    pub key_path: String,
+    /// When set, clients must present a certificate signed by this CA (mTLS).
+    #[serde(default)]
+    pub client_ca_path: Option<String>,
+}
+
+impl TlsSettings {
+    pub fn new(cert_path: &str, key_path: &str, client_ca_path: Option<&str>) -> Self {
+        Self {
+            cert_path: cert_path.to_string(),
+            key_path: key_path.to_string(),
+            client_ca_path: client_ca_path.map(|itm| itm.to_string()),
+        }
+    }
+
+    pub(crate) fn get_files(&self) -> Vec<String> {
+        let mut result = vec![
+            rust_extensions::file_utils::format_path(self.cert_path.as_str()).to_string(),
+            rust_extensions::file_utils::format_path(self.key_path.as_str()).to_string(),
+        ];
+
+        if let Some(client_ca_path) = self.client_ca_path.as_ref() {
+            result.push(
+                rust_extensions::file_utils::format_path(client_ca_path.as_str()).to_string(),
+            );
+        }
+
+        result
+    }
+}
+
+/// Implemented by the settings reader to take TLS certificates from the settings model.
+/// Passed to `ServiceContext::set_tls_from_settings`.
+#[async_trait]
+pub trait TlsSettingsReader {
+    async fn get_grpc_tls_settings(&self) -> Option<TlsSettings> {
+        None
+    }
+}